@@ -0,0 +1,341 @@
+use super::{
+    AudioBackend, BackendStream, ErrorCallback, InputCallback, OutputCallback, StreamConfig,
+    SupportedConfigRange,
+};
+use crate::audio::device::{assign_device_ids, is_vb_cable, DeviceInfo, IdCandidate};
+use crate::audio::error::AudioError;
+use crate::audio::sample_format::{
+    convert_input, convert_output, DeviceSample, Dither, SampleFormat,
+};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+/// 枚举到的设备：(id, 名称, 后端 id, 设备)
+type EnumeratedDevice = (String, String, Option<String>, cpal::Device);
+
+pub struct CpalBackend {
+    host: cpal::Host,
+}
+
+impl CpalBackend {
+    pub fn new() -> Self {
+        Self {
+            host: cpal::default_host(),
+        }
+    }
+
+    /// 枚举一个方向上的设备并分配 id
+    fn enumerate(&self, is_input: bool) -> Result<Vec<EnumeratedDevice>, AudioError> {
+        let devices: Vec<cpal::Device> = if is_input {
+            self.host.input_devices()?.collect()
+        } else {
+            self.host.output_devices()?.collect()
+        };
+
+        let mut entries = Vec::with_capacity(devices.len());
+        for device in devices {
+            let name = device.description()?.to_string();
+            let backend_id = device.id().ok().map(|id| id.to_string());
+            entries.push((name, backend_id, device));
+        }
+
+        let candidates: Vec<IdCandidate> = entries
+            .iter()
+            .map(|(name, backend_id, _)| IdCandidate {
+                name,
+                backend_id: backend_id.as_deref(),
+                is_input,
+            })
+            .collect();
+        let ids = assign_device_ids(&candidates);
+
+        Ok(ids
+            .into_iter()
+            .zip(entries)
+            .map(|(id, (name, backend_id, device))| (id, name, backend_id, device))
+            .collect())
+    }
+
+    fn find_device_by_id(
+        &self,
+        device_id: &str,
+        is_input: bool,
+    ) -> Result<cpal::Device, AudioError> {
+        self.enumerate(is_input)?
+            .into_iter()
+            .find(|(id, ..)| id == device_id)
+            .map(|(.., device)| device)
+            .ok_or_else(|| AudioError::DeviceNotFound(device_id.to_string()))
+    }
+
+    fn find_input_device_by_id(&self, device_id: &str) -> Result<cpal::Device, AudioError> {
+        self.find_device_by_id(device_id, true)
+    }
+
+    fn find_output_device_by_id(&self, device_id: &str) -> Result<cpal::Device, AudioError> {
+        self.find_device_by_id(device_id, false)
+    }
+}
+
+impl Default for CpalBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_cpal_config(config: &StreamConfig) -> cpal::StreamConfig {
+    cpal::StreamConfig {
+        channels: config.channels,
+        sample_rate: config.sample_rate,
+        buffer_size: match config.buffer_frames {
+            Some(frames) => cpal::BufferSize::Fixed(frames),
+            None => cpal::BufferSize::Default,
+        },
+    }
+}
+
+fn from_cpal_config(config: &cpal::SupportedStreamConfig) -> Result<StreamConfig, AudioError> {
+    let sample_format = from_cpal_format(config.sample_format()).ok_or_else(|| {
+        AudioError::Device(format!(
+            "Unsupported sample format: {}",
+            config.sample_format()
+        ))
+    })?;
+    Ok(StreamConfig {
+        sample_rate: config.sample_rate(),
+        channels: config.channels(),
+        buffer_frames: None,
+        sample_format,
+    })
+}
+
+/// 转换支持的配置范围，跳过引擎不支持的样本格式
+fn from_cpal_ranges(
+    ranges: impl Iterator<Item = cpal::SupportedStreamConfigRange>,
+) -> Vec<SupportedConfigRange> {
+    ranges
+        .filter_map(|range| {
+            let sample_format = from_cpal_format(range.sample_format())?;
+            let (min_buffer_frames, max_buffer_frames) = match range.buffer_size() {
+                cpal::SupportedBufferSize::Range { min, max } => (Some(*min), Some(*max)),
+                cpal::SupportedBufferSize::Unknown => (None, None),
+            };
+            Some(SupportedConfigRange {
+                channels: range.channels(),
+                min_sample_rate: range.min_sample_rate(),
+                max_sample_rate: range.max_sample_rate(),
+                min_buffer_frames,
+                max_buffer_frames,
+                sample_format,
+            })
+        })
+        .collect()
+}
+
+fn from_cpal_format(format: cpal::SampleFormat) -> Option<SampleFormat> {
+    match format {
+        cpal::SampleFormat::F32 => Some(SampleFormat::F32),
+        cpal::SampleFormat::I16 => Some(SampleFormat::I16),
+        cpal::SampleFormat::I32 => Some(SampleFormat::I32),
+        cpal::SampleFormat::U16 => Some(SampleFormat::U16),
+        cpal::SampleFormat::U8 => Some(SampleFormat::U8),
+        _ => None,
+    }
+}
+
+fn build_input<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut data_callback: InputCallback,
+    mut error_callback: ErrorCallback,
+) -> Result<cpal::Stream, AudioError>
+where
+    T: DeviceSample + cpal::SizedSample,
+{
+    let mut buffer: Vec<f32> = Vec::with_capacity(8192);
+    let stream = device.build_input_stream(
+        &to_cpal_config(config),
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            convert_input(data, &mut buffer);
+            data_callback(&buffer);
+        },
+        move |err| error_callback(err.to_string()),
+        None,
+    )?;
+    Ok(stream)
+}
+
+fn build_output<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut data_callback: OutputCallback,
+    mut error_callback: ErrorCallback,
+) -> Result<cpal::Stream, AudioError>
+where
+    T: DeviceSample + cpal::SizedSample,
+{
+    let mut buffer: Vec<f32> = Vec::with_capacity(8192);
+    let mut dither = Dither::new();
+    let stream = device.build_output_stream(
+        &to_cpal_config(config),
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            buffer.clear();
+            buffer.resize(data.len(), 0.0);
+            data_callback(&mut buffer);
+            convert_output(&buffer, data, &mut dither);
+        },
+        move |err| error_callback(err.to_string()),
+        None,
+    )?;
+    Ok(stream)
+}
+
+struct CpalStream(cpal::Stream);
+
+impl BackendStream for CpalStream {
+    fn play(&self) -> Result<(), AudioError> {
+        self.0.play()?;
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), AudioError> {
+        self.0
+            .pause()
+            .map_err(|e| AudioError::Device(e.to_string()))
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn name(&self) -> &'static str {
+        "cpal"
+    }
+
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, AudioError> {
+        let mut devices = Vec::new();
+
+        for is_input in [true, false] {
+            for (id, name, backend_id, device) in self.enumerate(is_input)? {
+                let config = if is_input {
+                    device.default_input_config()?
+                } else {
+                    device.default_output_config()?
+                };
+
+                devices.push(DeviceInfo {
+                    id,
+                    is_vb_cable: is_vb_cable(&name),
+                    name,
+                    backend_id,
+                    is_input,
+                    is_output: !is_input,
+                    sample_rate: config.sample_rate(),
+                    channels: config.channels(),
+                });
+            }
+        }
+
+        Ok(devices)
+    }
+
+    fn default_device_id(&self, is_input: bool) -> Result<Option<String>, AudioError> {
+        let default = if is_input {
+            self.host.default_input_device()
+        } else {
+            self.host.default_output_device()
+        };
+        let Some(default) = default else {
+            return Ok(None);
+        };
+
+        // 默认设备本身不带序号，按后端 id（没有时按名称）在枚举结果中查找
+        let backend_id = default.id().ok().map(|id| id.to_string());
+        let name = default.description()?.to_string();
+        Ok(self
+            .enumerate(is_input)?
+            .into_iter()
+            .find(|(_, n, b, _)| match (&backend_id, b) {
+                (Some(default_id), Some(b)) => default_id == b,
+                _ => *n == name,
+            })
+            .map(|(id, ..)| id))
+    }
+
+    fn default_input_config(&self, device_id: &str) -> Result<StreamConfig, AudioError> {
+        let device = self.find_input_device_by_id(device_id)?;
+        from_cpal_config(&device.default_input_config()?)
+    }
+
+    fn default_output_config(&self, device_id: &str) -> Result<StreamConfig, AudioError> {
+        let device = self.find_output_device_by_id(device_id)?;
+        from_cpal_config(&device.default_output_config()?)
+    }
+
+    fn supported_input_configs(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<SupportedConfigRange>, AudioError> {
+        let device = self.find_input_device_by_id(device_id)?;
+        let ranges = device
+            .supported_input_configs()
+            .map_err(|e| AudioError::Device(e.to_string()))?;
+        Ok(from_cpal_ranges(ranges))
+    }
+
+    fn supported_output_configs(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<SupportedConfigRange>, AudioError> {
+        let device = self.find_output_device_by_id(device_id)?;
+        let ranges = device
+            .supported_output_configs()
+            .map_err(|e| AudioError::Device(e.to_string()))?;
+        Ok(from_cpal_ranges(ranges))
+    }
+
+    fn open_input_stream(
+        &self,
+        device_id: &str,
+        config: &StreamConfig,
+        data_callback: InputCallback,
+        error_callback: ErrorCallback,
+    ) -> Result<Box<dyn BackendStream>, AudioError> {
+        let device = self.find_input_device_by_id(device_id)?;
+
+        let stream = match config.sample_format {
+            SampleFormat::F32 => build_input::<f32>(&device, config, data_callback, error_callback),
+            SampleFormat::I16 => build_input::<i16>(&device, config, data_callback, error_callback),
+            SampleFormat::I32 => build_input::<i32>(&device, config, data_callback, error_callback),
+            SampleFormat::U16 => build_input::<u16>(&device, config, data_callback, error_callback),
+            SampleFormat::U8 => build_input::<u8>(&device, config, data_callback, error_callback),
+        }?;
+
+        Ok(Box::new(CpalStream(stream)))
+    }
+
+    fn open_output_stream(
+        &self,
+        device_id: &str,
+        config: &StreamConfig,
+        data_callback: OutputCallback,
+        error_callback: ErrorCallback,
+    ) -> Result<Box<dyn BackendStream>, AudioError> {
+        let device = self.find_output_device_by_id(device_id)?;
+
+        let stream = match config.sample_format {
+            SampleFormat::F32 => {
+                build_output::<f32>(&device, config, data_callback, error_callback)
+            }
+            SampleFormat::I16 => {
+                build_output::<i16>(&device, config, data_callback, error_callback)
+            }
+            SampleFormat::I32 => {
+                build_output::<i32>(&device, config, data_callback, error_callback)
+            }
+            SampleFormat::U16 => {
+                build_output::<u16>(&device, config, data_callback, error_callback)
+            }
+            SampleFormat::U8 => build_output::<u8>(&device, config, data_callback, error_callback),
+        }?;
+
+        Ok(Box::new(CpalStream(stream)))
+    }
+}
@@ -0,0 +1,102 @@
+mod cpal_backend;
+mod virtual_backend;
+
+pub use cpal_backend::CpalBackend;
+pub use virtual_backend::{SignalSource, VirtualBackend, VirtualDevice};
+
+use super::{device::DeviceInfo, error::AudioError, sample_format::SampleFormat};
+use serde::{Deserialize, Serialize};
+
+/// 输入回调：收到交错(interleaved)的 f32 样本
+pub type InputCallback = Box<dyn FnMut(&[f32]) + Send + 'static>;
+/// 输出回调：填充交错的 f32 输出缓冲区
+pub type OutputCallback = Box<dyn FnMut(&mut [f32]) + Send + 'static>;
+/// 流错误回调
+pub type ErrorCallback = Box<dyn FnMut(String) + Send + 'static>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamConfig {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// None 表示使用后端默认缓冲区大小
+    pub buffer_frames: Option<u32>,
+    /// 设备侧的样本格式；回调中的数据总是 f32，由后端负责转换
+    pub sample_format: SampleFormat,
+}
+
+/// 设备支持的一组配置：固定声道数和样本格式下的采样率、缓冲区范围
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportedConfigRange {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    /// 后端无法提供缓冲区范围时为 None
+    pub min_buffer_frames: Option<u32>,
+    pub max_buffer_frames: Option<u32>,
+    pub sample_format: SampleFormat,
+}
+
+impl SupportedConfigRange {
+    pub fn supports(&self, config: &StreamConfig) -> bool {
+        let buffer_ok = match (
+            config.buffer_frames,
+            self.min_buffer_frames,
+            self.max_buffer_frames,
+        ) {
+            (Some(frames), Some(min), Some(max)) => (min..=max).contains(&frames),
+            _ => true,
+        };
+        self.channels == config.channels
+            && (self.min_sample_rate..=self.max_sample_rate).contains(&config.sample_rate)
+            && buffer_ok
+    }
+}
+
+/// 已打开的设备流，drop 时释放设备
+pub trait BackendStream: Send {
+    fn play(&self) -> Result<(), AudioError>;
+    fn pause(&self) -> Result<(), AudioError>;
+}
+
+/// 音频后端：负责设备枚举和打开输入/输出流。
+/// AudioEngine 只通过这个 trait 访问设备，因此可以在没有声卡的环境下
+/// 使用 VirtualBackend 运行。
+pub trait AudioBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, AudioError>;
+
+    /// 系统当前的默认输入或输出设备 id；没有默认设备时返回 None
+    fn default_device_id(&self, is_input: bool) -> Result<Option<String>, AudioError>;
+
+    fn default_input_config(&self, device_id: &str) -> Result<StreamConfig, AudioError>;
+
+    fn default_output_config(&self, device_id: &str) -> Result<StreamConfig, AudioError>;
+
+    /// 设备支持的全部输入配置；后端无法枚举时返回空列表
+    fn supported_input_configs(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<SupportedConfigRange>, AudioError>;
+
+    fn supported_output_configs(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<SupportedConfigRange>, AudioError>;
+
+    fn open_input_stream(
+        &self,
+        device_id: &str,
+        config: &StreamConfig,
+        data_callback: InputCallback,
+        error_callback: ErrorCallback,
+    ) -> Result<Box<dyn BackendStream>, AudioError>;
+
+    fn open_output_stream(
+        &self,
+        device_id: &str,
+        config: &StreamConfig,
+        data_callback: OutputCallback,
+        error_callback: ErrorCallback,
+    ) -> Result<Box<dyn BackendStream>, AudioError>;
+}
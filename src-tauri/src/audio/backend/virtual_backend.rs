@@ -0,0 +1,558 @@
+use super::{
+    AudioBackend, BackendStream, ErrorCallback, InputCallback, OutputCallback, StreamConfig,
+    SupportedConfigRange,
+};
+use crate::audio::device::DeviceInfo;
+use crate::audio::error::AudioError;
+use crate::audio::sample_format::{
+    convert_input, convert_output, DeviceSample, Dither, SampleFormat,
+};
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// 虚拟输入设备的信号源：每个时钟周期填充一块交错样本
+pub type SignalSource = Box<dyn FnMut(&mut [f32]) + Send + 'static>;
+
+#[derive(Clone, Debug)]
+pub struct VirtualDevice {
+    pub id: String,
+    pub name: String,
+    pub is_input: bool,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// 设备时钟相对标称采样率的偏差（ppm），用于模拟时钟漂移
+    pub clock_drift_ppm: f64,
+    /// 设备原生样本格式，整数格式的数据会经过量化
+    pub sample_format: SampleFormat,
+    /// 设备接受的配置；为空时只支持默认配置
+    pub supported_configs: Vec<SupportedConfigRange>,
+}
+
+impl VirtualDevice {
+    pub fn input(id: &str, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            is_input: true,
+            sample_rate,
+            channels,
+            clock_drift_ppm: 0.0,
+            sample_format: SampleFormat::F32,
+            supported_configs: Vec::new(),
+        }
+    }
+
+    pub fn output(id: &str, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            is_input: false,
+            sample_rate,
+            channels,
+            clock_drift_ppm: 0.0,
+            sample_format: SampleFormat::F32,
+            supported_configs: Vec::new(),
+        }
+    }
+
+    pub fn with_clock_drift(mut self, ppm: f64) -> Self {
+        self.clock_drift_ppm = ppm;
+        self
+    }
+
+    pub fn with_sample_format(mut self, sample_format: SampleFormat) -> Self {
+        self.sample_format = sample_format;
+        self
+    }
+
+    pub fn with_supported_configs(mut self, configs: Vec<SupportedConfigRange>) -> Self {
+        self.supported_configs = configs;
+        self
+    }
+
+    fn supported_ranges(&self) -> Vec<SupportedConfigRange> {
+        if !self.supported_configs.is_empty() {
+            return self.supported_configs.clone();
+        }
+        vec![SupportedConfigRange {
+            channels: self.channels,
+            min_sample_rate: self.sample_rate,
+            max_sample_rate: self.sample_rate,
+            min_buffer_frames: None,
+            max_buffer_frames: None,
+            sample_format: self.sample_format,
+        }]
+    }
+}
+
+struct DeviceSlot {
+    device: VirtualDevice,
+    source: Option<SignalSource>,
+    captured: Vec<f32>,
+}
+
+enum StreamCallback {
+    Input(InputCallback),
+    Output(OutputCallback),
+}
+
+struct StreamSlot {
+    id: u64,
+    device_id: String,
+    config: StreamConfig,
+    callback: StreamCallback,
+    error_callback: ErrorCallback,
+    playing: bool,
+    /// 出错后的流不再被驱动，与真实后端中设备断开后的流一样
+    failed: bool,
+    clock_rate: f64,
+    frame_remainder: f64,
+    scratch: Vec<f32>,
+    dither: Dither,
+}
+
+struct Inner {
+    devices: Mutex<Vec<DeviceSlot>>,
+    streams: Mutex<Vec<StreamSlot>>,
+    /// 显式指定的默认输入和输出设备；未指定时使用最先注册的设备
+    default_input: Mutex<Option<String>>,
+    default_output: Mutex<Option<String>>,
+    next_stream_id: AtomicU64,
+    block_duration: Duration,
+    clock_running: AtomicBool,
+    clock_thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+/// 进程内的虚拟音频后端。
+///
+/// 设备由测试代码注册；回调既可以通过 `process_block` 手动、确定性地驱动，
+/// 也可以通过 `start_clock` 由时钟线程按实时速度驱动。
+/// 每个周期先调用所有输入流，再调用所有输出流。
+#[derive(Clone)]
+pub struct VirtualBackend {
+    inner: Arc<Inner>,
+}
+
+impl VirtualBackend {
+    pub fn new() -> Self {
+        Self::with_block_duration(Duration::from_millis(10))
+    }
+
+    pub fn with_block_duration(block_duration: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                devices: Mutex::new(Vec::new()),
+                streams: Mutex::new(Vec::new()),
+                default_input: Mutex::new(None),
+                default_output: Mutex::new(None),
+                next_stream_id: AtomicU64::new(0),
+                block_duration,
+                clock_running: AtomicBool::new(false),
+                clock_thread: Mutex::new(None),
+            }),
+        }
+    }
+
+    pub fn block_duration(&self) -> Duration {
+        self.inner.block_duration
+    }
+
+    pub fn add_device(&self, device: VirtualDevice) {
+        let mut devices = self.inner.devices.lock();
+        devices.retain(|slot| slot.device.id != device.id);
+        devices.push(DeviceSlot {
+            device,
+            source: None,
+            captured: Vec::new(),
+        });
+    }
+
+    /// 移除设备，模拟拔出：该设备上的流收到错误回调并停止
+    pub fn remove_device(&self, device_id: &str) {
+        self.inner
+            .devices
+            .lock()
+            .retain(|slot| slot.device.id != device_id);
+        self.fail_device(device_id, "Device disconnected");
+    }
+
+    /// 让设备上所有的流出错：调用错误回调，之后不再驱动这些流
+    pub fn fail_device(&self, device_id: &str, message: &str) {
+        for slot in self.inner.streams.lock().iter_mut() {
+            if slot.device_id == device_id && !slot.failed {
+                slot.failed = true;
+                (slot.error_callback)(message.to_string());
+            }
+        }
+    }
+
+    /// 把设备设为其方向上的默认设备
+    pub fn set_default_device(&self, device_id: &str) -> Result<(), AudioError> {
+        let is_input = self
+            .inner
+            .devices
+            .lock()
+            .iter()
+            .find(|slot| slot.device.id == device_id)
+            .map(|slot| slot.device.is_input)
+            .ok_or_else(|| AudioError::DeviceNotFound(device_id.to_string()))?;
+        let default = if is_input {
+            &self.inner.default_input
+        } else {
+            &self.inner.default_output
+        };
+        *default.lock() = Some(device_id.to_string());
+        Ok(())
+    }
+
+    /// 设置输入设备的信号源；未设置时输入静音
+    pub fn set_source(&self, device_id: &str, source: SignalSource) -> Result<(), AudioError> {
+        let mut devices = self.inner.devices.lock();
+        let slot = devices
+            .iter_mut()
+            .find(|slot| slot.device.id == device_id && slot.device.is_input)
+            .ok_or_else(|| AudioError::DeviceNotFound(device_id.to_string()))?;
+        slot.source = Some(source);
+        Ok(())
+    }
+
+    /// 取出输出设备至今收到的全部样本（交错格式）
+    pub fn take_output(&self, device_id: &str) -> Vec<f32> {
+        self.inner
+            .devices
+            .lock()
+            .iter_mut()
+            .find(|slot| slot.device.id == device_id)
+            .map(|slot| std::mem::take(&mut slot.captured))
+            .unwrap_or_default()
+    }
+
+    pub fn active_stream_count(&self) -> usize {
+        self.inner.streams.lock().len()
+    }
+
+    /// 驱动一个时钟周期
+    pub fn process_block(&self) {
+        self.inner.process_block();
+    }
+
+    pub fn start_clock(&self) {
+        if self.inner.clock_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let weak = Arc::downgrade(&self.inner);
+        let handle = thread::Builder::new()
+            .name("virtual-audio-clock".to_string())
+            .spawn(move || run_clock(weak))
+            .expect("failed to spawn virtual clock thread");
+
+        *self.inner.clock_thread.lock() = Some(handle);
+    }
+
+    pub fn stop_clock(&self) {
+        self.inner.clock_running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.inner.clock_thread.lock().take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn find_device(&self, device_id: &str, is_input: bool) -> Result<VirtualDevice, AudioError> {
+        self.inner
+            .devices
+            .lock()
+            .iter()
+            .find(|slot| slot.device.id == device_id && slot.device.is_input == is_input)
+            .map(|slot| slot.device.clone())
+            .ok_or_else(|| AudioError::DeviceNotFound(device_id.to_string()))
+    }
+
+    fn register_stream(
+        &self,
+        device: &VirtualDevice,
+        config: &StreamConfig,
+        callback: StreamCallback,
+        error_callback: ErrorCallback,
+    ) -> Box<dyn BackendStream> {
+        let id = self.inner.next_stream_id.fetch_add(1, Ordering::SeqCst);
+        self.inner.streams.lock().push(StreamSlot {
+            id,
+            device_id: device.id.clone(),
+            config: *config,
+            callback,
+            error_callback,
+            playing: false,
+            failed: false,
+            clock_rate: config.sample_rate as f64 * (1.0 + device.clock_drift_ppm * 1e-6),
+            frame_remainder: 0.0,
+            scratch: Vec::new(),
+            dither: Dither::new(),
+        });
+
+        Box::new(VirtualStream {
+            inner: Arc::downgrade(&self.inner),
+            id,
+        })
+    }
+}
+
+impl Default for VirtualBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn run_clock(weak: Weak<Inner>) {
+    let mut next_tick = Instant::now();
+    loop {
+        let Some(inner) = weak.upgrade() else {
+            break;
+        };
+        if !inner.clock_running.load(Ordering::SeqCst) {
+            break;
+        }
+
+        inner.process_block();
+        next_tick += inner.block_duration;
+        drop(inner);
+
+        let now = Instant::now();
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        } else {
+            next_tick = now;
+        }
+    }
+}
+
+impl Inner {
+    fn process_block(&self) {
+        let mut streams = self.streams.lock();
+        let block_secs = self.block_duration.as_secs_f64();
+
+        // 先运行输入流，保证同一周期内输出能读到本周期的输入
+        for pass_input in [true, false] {
+            for slot in streams.iter_mut() {
+                let is_input = matches!(slot.callback, StreamCallback::Input(_));
+                if is_input != pass_input || !slot.playing || slot.failed {
+                    continue;
+                }
+
+                let exact = slot.clock_rate * block_secs + slot.frame_remainder;
+                let frames = exact.floor();
+                slot.frame_remainder = exact - frames;
+
+                let len = frames as usize * slot.config.channels as usize;
+                slot.scratch.clear();
+                slot.scratch.resize(len, 0.0);
+
+                match &mut slot.callback {
+                    StreamCallback::Input(callback) => {
+                        if let Some(source) = self
+                            .devices
+                            .lock()
+                            .iter_mut()
+                            .find(|d| d.device.id == slot.device_id)
+                            .and_then(|d| d.source.as_mut())
+                        {
+                            source(&mut slot.scratch);
+                        }
+                        quantize(slot.config.sample_format, &mut slot.scratch, None);
+                        callback(&slot.scratch);
+                    }
+                    StreamCallback::Output(callback) => {
+                        callback(&mut slot.scratch);
+                        quantize(
+                            slot.config.sample_format,
+                            &mut slot.scratch,
+                            Some(&mut slot.dither),
+                        );
+                        if let Some(device) = self
+                            .devices
+                            .lock()
+                            .iter_mut()
+                            .find(|d| d.device.id == slot.device_id)
+                        {
+                            device.captured.extend_from_slice(&slot.scratch);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 模拟设备的样本格式：数据转换为设备格式再转回 f32。
+/// 输出方向与真实后端一样加抖动
+fn quantize(format: SampleFormat, samples: &mut Vec<f32>, dither: Option<&mut Dither>) {
+    match format {
+        SampleFormat::F32 => {}
+        SampleFormat::I16 => round_trip::<i16>(samples, dither),
+        SampleFormat::I32 => round_trip::<i32>(samples, dither),
+        SampleFormat::U16 => round_trip::<u16>(samples, dither),
+        SampleFormat::U8 => round_trip::<u8>(samples, dither),
+    }
+}
+
+fn round_trip<T: DeviceSample + Default + Clone>(
+    samples: &mut Vec<f32>,
+    dither: Option<&mut Dither>,
+) {
+    let device: Vec<T> = match dither {
+        Some(dither) => {
+            let mut device = vec![T::default(); samples.len()];
+            convert_output(samples, &mut device, dither);
+            device
+        }
+        None => samples.iter().map(|&s| T::from_f32(s)).collect(),
+    };
+    convert_input(&device, samples);
+}
+
+struct VirtualStream {
+    inner: Weak<Inner>,
+    id: u64,
+}
+
+impl VirtualStream {
+    fn set_playing(&self, playing: bool) -> Result<(), AudioError> {
+        let inner = self
+            .inner
+            .upgrade()
+            .ok_or_else(|| AudioError::Device("virtual backend dropped".to_string()))?;
+        let mut streams = inner.streams.lock();
+        if let Some(slot) = streams.iter_mut().find(|s| s.id == self.id) {
+            slot.playing = playing;
+        }
+        Ok(())
+    }
+}
+
+impl BackendStream for VirtualStream {
+    fn play(&self) -> Result<(), AudioError> {
+        self.set_playing(true)
+    }
+
+    fn pause(&self) -> Result<(), AudioError> {
+        self.set_playing(false)
+    }
+}
+
+impl Drop for VirtualStream {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.streams.lock().retain(|s| s.id != self.id);
+        }
+    }
+}
+
+impl AudioBackend for VirtualBackend {
+    fn name(&self) -> &'static str {
+        "virtual"
+    }
+
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, AudioError> {
+        Ok(self
+            .inner
+            .devices
+            .lock()
+            .iter()
+            .map(|slot| DeviceInfo {
+                id: slot.device.id.clone(),
+                name: slot.device.name.clone(),
+                backend_id: None,
+                is_input: slot.device.is_input,
+                is_output: !slot.device.is_input,
+                sample_rate: slot.device.sample_rate,
+                channels: slot.device.channels,
+                is_vb_cable: false,
+            })
+            .collect())
+    }
+
+    fn default_device_id(&self, is_input: bool) -> Result<Option<String>, AudioError> {
+        let explicit = if is_input {
+            self.inner.default_input.lock().clone()
+        } else {
+            self.inner.default_output.lock().clone()
+        };
+        let devices = self.inner.devices.lock();
+        let mut candidates = devices.iter().filter(|s| s.device.is_input == is_input);
+        Ok(explicit
+            .filter(|id| devices.iter().any(|s| s.device.id == *id))
+            .or_else(|| candidates.next().map(|s| s.device.id.clone())))
+    }
+
+    fn default_input_config(&self, device_id: &str) -> Result<StreamConfig, AudioError> {
+        let device = self.find_device(device_id, true)?;
+        Ok(StreamConfig {
+            sample_rate: device.sample_rate,
+            channels: device.channels,
+            buffer_frames: None,
+            sample_format: device.sample_format,
+        })
+    }
+
+    fn default_output_config(&self, device_id: &str) -> Result<StreamConfig, AudioError> {
+        let device = self.find_device(device_id, false)?;
+        Ok(StreamConfig {
+            sample_rate: device.sample_rate,
+            channels: device.channels,
+            buffer_frames: None,
+            sample_format: device.sample_format,
+        })
+    }
+
+    fn supported_input_configs(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<SupportedConfigRange>, AudioError> {
+        Ok(self.find_device(device_id, true)?.supported_ranges())
+    }
+
+    fn supported_output_configs(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<SupportedConfigRange>, AudioError> {
+        Ok(self.find_device(device_id, false)?.supported_ranges())
+    }
+
+    fn open_input_stream(
+        &self,
+        device_id: &str,
+        config: &StreamConfig,
+        data_callback: InputCallback,
+        error_callback: ErrorCallback,
+    ) -> Result<Box<dyn BackendStream>, AudioError> {
+        let device = self.find_device(device_id, true)?;
+        Ok(self.register_stream(
+            &device,
+            config,
+            StreamCallback::Input(data_callback),
+            error_callback,
+        ))
+    }
+
+    fn open_output_stream(
+        &self,
+        device_id: &str,
+        config: &StreamConfig,
+        data_callback: OutputCallback,
+        error_callback: ErrorCallback,
+    ) -> Result<Box<dyn BackendStream>, AudioError> {
+        let device = self.find_device(device_id, false)?;
+        Ok(self.register_stream(
+            &device,
+            config,
+            StreamCallback::Output(data_callback),
+            error_callback,
+        ))
+    }
+}
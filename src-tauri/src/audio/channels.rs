@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_1_SQRT_2;
+
+/// 路由的声道映射矩阵。
+///
+/// `matrix[out][in]` 是输入声道 `in` 混入输出声道 `out` 的增益，
+/// 例如单声道到立体声为 `[[1.0], [1.0]]`。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelMap {
+    pub matrix: Vec<Vec<f32>>,
+}
+
+impl ChannelMap {
+    pub fn new(matrix: Vec<Vec<f32>>) -> Self {
+        Self { matrix }
+    }
+
+    pub fn identity(channels: u16) -> Self {
+        Self::from_fn(
+            channels,
+            channels,
+            |out, input| {
+                if out == input {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+        )
+    }
+
+    /// 根据输入输出声道数生成默认映射：
+    /// 单声道复制到 L+R，立体声平均为单声道，5.1 按 ITU 系数缩混为立体声，
+    /// 其余情况按声道序号一一对应。
+    pub fn default_for(input_channels: u16, output_channels: u16) -> Self {
+        match (input_channels, output_channels) {
+            (i, o) if i == o => Self::identity(i),
+            (1, o) => Self::from_fn(1, o, |out, _| if out < 2 { 1.0 } else { 0.0 }),
+            (2, 1) => Self::new(vec![vec![0.5, 0.5]]),
+            // 5.1 声道顺序：L R C LFE Ls Rs，LFE 不参与缩混
+            (6, 2) => Self::new(vec![
+                vec![1.0, 0.0, FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2, 0.0],
+                vec![0.0, 1.0, FRAC_1_SQRT_2, 0.0, 0.0, FRAC_1_SQRT_2],
+            ]),
+            (6, 1) => Self::new(vec![vec![
+                0.5,
+                0.5,
+                FRAC_1_SQRT_2,
+                0.0,
+                0.5 * FRAC_1_SQRT_2,
+                0.5 * FRAC_1_SQRT_2,
+            ]]),
+            (i, o) => Self::from_fn(i, o, |out, input| if out == input { 1.0 } else { 0.0 }),
+        }
+    }
+
+    fn from_fn(input_channels: u16, output_channels: u16, f: impl Fn(usize, usize) -> f32) -> Self {
+        Self::new(
+            (0..output_channels as usize)
+                .map(|out| {
+                    (0..input_channels as usize)
+                        .map(|input| f(out, input))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn input_channels(&self) -> usize {
+        self.matrix.first().map_or(0, Vec::len)
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.matrix.len()
+    }
+
+    /// 矩阵非空且每行长度一致时才有效
+    pub fn is_valid(&self) -> bool {
+        let inputs = self.input_channels();
+        inputs > 0 && self.matrix.iter().all(|row| row.len() == inputs)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.input_channels() == self.output_channels()
+            && self.matrix.iter().enumerate().all(|(out, row)| {
+                row.iter()
+                    .enumerate()
+                    .all(|(input, &g)| g == if out == input { 1.0 } else { 0.0 })
+            })
+    }
+
+    /// 将交错格式的输入帧映射到输出声道，结果追加到 output
+    pub fn apply(&self, input: &[f32], output: &mut Vec<f32>) {
+        let input_channels = self.input_channels();
+        if input_channels == 0 {
+            return;
+        }
+
+        for frame in input.chunks_exact(input_channels) {
+            for row in &self.matrix {
+                output.push(row.iter().zip(frame).map(|(g, s)| g * s).sum());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mono_to_stereo_duplicates_channel() {
+        let map = ChannelMap::default_for(1, 2);
+        let mut output = Vec::new();
+        map.apply(&[0.1, 0.2, 0.3], &mut output);
+        assert_eq!(output, vec![0.1, 0.1, 0.2, 0.2, 0.3, 0.3]);
+    }
+
+    #[test]
+    fn test_surround_downmix_drops_lfe() {
+        let map = ChannelMap::default_for(6, 2);
+        let mut output = Vec::new();
+        map.apply(&[1.0, 0.0, 0.0, 1.0, 0.0, 0.0], &mut output);
+        assert_eq!(output, vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_custom_map_routes_single_input_channel() {
+        // 输入声道 1 -> 输出 L+R
+        let map = ChannelMap::new(vec![vec![1.0, 0.0], vec![1.0, 0.0]]);
+        let mut output = Vec::new();
+        map.apply(&[0.4, 0.9], &mut output);
+        assert_eq!(output, vec![0.4, 0.4]);
+    }
+}
@@ -1,11 +1,19 @@
-use cpal::traits::{DeviceTrait, HostTrait};
+use super::backend::{AudioBackend, CpalBackend, StreamConfig, SupportedConfigRange};
+use super::sample_format::SampleFormat;
+use super::watcher::{DeviceEvent, DeviceSnapshot, DeviceWatcher};
+use super::AudioError;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DeviceInfo {
     pub id: String,
     pub name: String,
+    /// 后端提供的持久设备 id（如果有）
+    #[serde(default)]
+    pub backend_id: Option<String>,
     pub is_input: bool,
     pub is_output: bool,
     pub sample_rate: u32,
@@ -13,75 +21,242 @@ pub struct DeviceInfo {
     pub is_vb_cable: bool,
 }
 
+/// 用户为设备指定的流参数，未指定的项使用设备默认值
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    pub sample_rate: Option<u32>,
+    pub buffer_frames: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+impl DeviceConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 设备某一方向（输入或输出）的默认配置和全部支持的配置，
+/// 以及汇总后的格式、采样率、声道和缓冲区范围
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceCapabilities {
+    pub device_id: String,
+    pub name: String,
+    pub is_input: bool,
+    pub default_config: StreamConfig,
+    pub supported_configs: Vec<SupportedConfigRange>,
+    pub sample_formats: Vec<SampleFormat>,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub channel_counts: Vec<u16>,
+    pub min_buffer_frames: Option<u32>,
+    pub max_buffer_frames: Option<u32>,
+}
+
+impl DeviceCapabilities {
+    fn new(
+        info: &DeviceInfo,
+        is_input: bool,
+        default_config: StreamConfig,
+        supported_configs: Vec<SupportedConfigRange>,
+    ) -> Self {
+        // 后端无法枚举时，至少报告默认配置
+        let ranges: &[SupportedConfigRange] = &supported_configs;
+        let mut sample_formats: Vec<SampleFormat> =
+            ranges.iter().map(|r| r.sample_format).collect();
+        sample_formats.push(default_config.sample_format);
+        sample_formats.sort_by_key(|f| *f as u8);
+        sample_formats.dedup();
+
+        let mut channel_counts: Vec<u16> = ranges.iter().map(|r| r.channels).collect();
+        channel_counts.push(default_config.channels);
+        channel_counts.sort_unstable();
+        channel_counts.dedup();
+
+        Self {
+            device_id: info.id.clone(),
+            name: info.name.clone(),
+            is_input,
+            sample_formats,
+            min_sample_rate: ranges
+                .iter()
+                .map(|r| r.min_sample_rate)
+                .fold(default_config.sample_rate, u32::min),
+            max_sample_rate: ranges
+                .iter()
+                .map(|r| r.max_sample_rate)
+                .fold(default_config.sample_rate, u32::max),
+            channel_counts,
+            min_buffer_frames: ranges.iter().filter_map(|r| r.min_buffer_frames).min(),
+            max_buffer_frames: ranges.iter().filter_map(|r| r.max_buffer_frames).max(),
+            default_config,
+            supported_configs,
+        }
+    }
+}
+
 pub struct DeviceManager {
-    host: cpal::Host,
+    backend: Arc<dyn AudioBackend>,
 }
 
 impl DeviceManager {
     pub fn new() -> Self {
-        Self {
-            host: cpal::default_host(),
-        }
+        Self::with_backend(Arc::new(CpalBackend::new()))
+    }
+
+    pub fn with_backend(backend: Arc<dyn AudioBackend>) -> Self {
+        Self { backend }
+    }
+
+    pub fn backend(&self) -> &Arc<dyn AudioBackend> {
+        &self.backend
     }
 
     pub fn list_devices(&self) -> Result<Vec<DeviceInfo>, super::AudioError> {
-        let mut devices = Vec::new();
-
-        for device in self.host.input_devices()? {
-            let name = device.description()?.to_string();
-            let config = device.default_input_config()?;
-
-            devices.push(DeviceInfo {
-                id: self.generate_device_id(&name, true),
-                name: name.clone(),
-                is_input: true,
-                is_output: false,
-                sample_rate: config.sample_rate(),
-                channels: config.channels(),
-                is_vb_cable: self.is_vb_cable(&name),
-            });
-        }
+        self.backend.list_devices()
+    }
 
-        for device in self.host.output_devices()? {
-            let name = device.description()?.to_string();
-            let config = device.default_output_config()?;
-
-            devices.push(DeviceInfo {
-                id: self.generate_device_id(&name, false),
-                name: name.clone(),
-                is_input: false,
-                is_output: true,
-                sample_rate: config.sample_rate(),
-                channels: config.channels(),
-                is_vb_cable: self.is_vb_cable(&name),
-            });
+    /// 当前的设备列表和默认设备
+    pub fn snapshot(&self) -> Result<DeviceSnapshot, AudioError> {
+        DeviceSnapshot::capture(self.backend.as_ref())
+    }
+
+    /// 启动后台线程，每隔 interval 比较一次设备列表并回调变化
+    pub fn watch<F>(&self, interval: Duration, on_event: F) -> DeviceWatcher
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        DeviceWatcher::spawn(self.backend.clone(), interval, on_event)
+    }
+
+    /// 把旧配置中的设备 id 映射到当前设备列表中的 id，只返回有变化的 id。
+    /// 只有旧格式的 id 需要映射，没有时不枚举设备；所有 id 共用同一次枚举的结果
+    pub fn resolve_device_ids<'a>(
+        &self,
+        device_ids: impl IntoIterator<Item = &'a String>,
+    ) -> HashMap<String, String> {
+        let legacy: Vec<&String> = device_ids
+            .into_iter()
+            .filter(|id| parse_legacy_device_id(id).is_some())
+            .collect();
+        if legacy.is_empty() {
+            return HashMap::new();
         }
+        let devices = match self.list_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                tracing::warn!("Failed to list devices: {}", e);
+                return HashMap::new();
+            }
+        };
+        legacy
+            .into_iter()
+            .filter_map(|id| {
+                let resolved = match_device_id(id, &devices)?;
+                (resolved != *id).then(|| {
+                    tracing::info!("Resolved device id {} -> {}", id, resolved);
+                    (id.clone(), resolved)
+                })
+            })
+            .collect()
+    }
+
+    /// 设备的能力，同名的输入和输出各返回一项
+    pub fn capabilities(&self, device_id: &str) -> Result<Vec<DeviceCapabilities>, AudioError> {
+        let devices = self.list_devices()?;
+        let info = devices
+            .iter()
+            .find(|d| d.id == device_id)
+            .ok_or_else(|| AudioError::DeviceNotFound(device_id.to_string()))?;
+        self.capabilities_for(info)
+    }
 
-        Ok(devices)
+    /// 所有设备的能力。单个设备查询失败（例如刚被拔出）时跳过它
+    pub fn list_capabilities(&self) -> Result<Vec<DeviceCapabilities>, AudioError> {
+        let mut all = Vec::new();
+        for info in self.list_devices()? {
+            match self.capabilities_for(&info) {
+                Ok(capabilities) => all.extend(capabilities),
+                Err(e) => tracing::warn!("Failed to query capabilities of {}: {}", info.id, e),
+            }
+        }
+        Ok(all)
     }
 
-    fn generate_device_id(&self, name: &str, is_input: bool) -> String {
-        let normalized_name = self.normalize_device_name(name);
-        format!(
-            "{}_{}",
-            normalized_name,
-            if is_input { "input" } else { "output" }
-        )
+    fn capabilities_for(&self, info: &DeviceInfo) -> Result<Vec<DeviceCapabilities>, AudioError> {
+        let mut capabilities = Vec::new();
+        if info.is_input {
+            capabilities.push(DeviceCapabilities::new(
+                info,
+                true,
+                self.backend.default_input_config(&info.id)?,
+                self.backend.supported_input_configs(&info.id)?,
+            ));
+        }
+        if info.is_output {
+            capabilities.push(DeviceCapabilities::new(
+                info,
+                false,
+                self.backend.default_output_config(&info.id)?,
+                self.backend.supported_output_configs(&info.id)?,
+            ));
+        }
+        Ok(capabilities)
     }
 
-    fn normalize_device_name(&self, name: &str) -> String {
-        // 在Windows上，WASAPI设备描述可能包含" via "后缀
-        // 例如："CABLE Output via Line Input"
-        // 我们只保留" via "之前的部分作为规范名称
-        if let Some(pos) = name.find(" via ") {
-            name[..pos].trim().to_string()
+    /// 在设备默认配置上应用用户指定的参数，并检查设备是否支持。
+    /// 优先保留默认样本格式；后端无法枚举支持的配置时直接接受
+    pub fn resolve_stream_config(
+        &self,
+        device_id: &str,
+        is_input: bool,
+        overrides: &DeviceConfig,
+    ) -> Result<StreamConfig, AudioError> {
+        let default = if is_input {
+            self.backend.default_input_config(device_id)?
         } else {
-            name.trim().to_string()
+            self.backend.default_output_config(device_id)?
+        };
+        if overrides.is_empty() {
+            return Ok(default);
         }
-    }
 
-    fn is_vb_cable(&self, name: &str) -> bool {
-        name.contains("Cable") || name.contains("VB Audio Cable")
+        let mut config = StreamConfig {
+            sample_rate: overrides.sample_rate.unwrap_or(default.sample_rate),
+            channels: overrides.channels.unwrap_or(default.channels),
+            buffer_frames: overrides.buffer_frames.or(default.buffer_frames),
+            sample_format: default.sample_format,
+        };
+
+        let supported = if is_input {
+            self.backend.supported_input_configs(device_id)?
+        } else {
+            self.backend.supported_output_configs(device_id)?
+        };
+        if supported.is_empty() {
+            return Ok(config);
+        }
+
+        let matching: Vec<&SupportedConfigRange> =
+            supported.iter().filter(|r| r.supports(&config)).collect();
+        let range = matching
+            .iter()
+            .find(|r| r.sample_format == default.sample_format)
+            .or(matching.first())
+            .ok_or_else(|| {
+                AudioError::Config(format!(
+                    "Device {} does not support {} channels at {} Hz{}",
+                    device_id,
+                    config.channels,
+                    config.sample_rate,
+                    config
+                        .buffer_frames
+                        .map(|f| format!(" with {} frame buffers", f))
+                        .unwrap_or_default()
+                ))
+            })?;
+        config.sample_format = range.sample_format;
+        Ok(config)
     }
 }
 
@@ -90,3 +265,294 @@ impl Default for DeviceManager {
         Self::new()
     }
 }
+
+/// 名称相似度匹配的最低得分
+const MIN_NAME_SIMILARITY: f64 = 0.75;
+
+/// 分配设备 id 所需的枚举信息
+pub(crate) struct IdCandidate<'a> {
+    pub name: &'a str,
+    pub backend_id: Option<&'a str>,
+    pub is_input: bool,
+}
+
+/// 为一次枚举得到的设备分配 id。
+///
+/// 优先使用后端提供的持久 id（WASAPI endpoint id、CoreAudio UID、ALSA 设备名等），
+/// 重启后保持不变；没有时使用完整的设备名。两种情况都带方向前缀，
+/// 仍然重复的 id（例如两个相同型号的 USB 声卡）按枚举顺序追加 `#2`、`#3`。
+///
+/// 注意：后端不提供持久 id 时，`#n` 取决于枚举顺序，重启或重新插拔后
+/// 两个同名设备的编号可能互换，路由随之落到另一台设备上。
+/// 没有其他可以区分两台同名设备的稳定信息，因此这种情况下只保证同一次运行内不变。
+pub(crate) fn assign_device_ids(candidates: &[IdCandidate]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    candidates
+        .iter()
+        .map(|candidate| {
+            let base = format!(
+                "{}:{}",
+                direction(candidate.is_input),
+                candidate.backend_id.unwrap_or(candidate.name)
+            );
+            let count = seen.entry(base.clone()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                base
+            } else {
+                format!("{}#{}", base, count)
+            }
+        })
+        .collect()
+}
+
+fn direction(is_input: bool) -> &'static str {
+    if is_input {
+        "input"
+    } else {
+        "output"
+    }
+}
+
+/// 在当前设备列表中查找 id 对应的设备。
+///
+/// 当前格式的 id 只做精确匹配：找不到说明设备暂时不在（例如两个相同型号的声卡中
+/// `#2` 被拔掉），保持原 id，设备重新插入后由流恢复重新打开，
+/// 而不是把路由转移到同名的另一台设备上。旧版 `{名称}_input` 格式的 id
+/// 依次尝试旧 id 方案和按名称相似度匹配，使升级前保存的路由在设备名略有变化后仍能找到设备
+pub(crate) fn match_device_id(device_id: &str, devices: &[DeviceInfo]) -> Option<String> {
+    if devices.iter().any(|d| d.id == device_id) {
+        return Some(device_id.to_string());
+    }
+
+    let (is_input, name) = parse_legacy_device_id(device_id)?;
+    devices
+        .iter()
+        .filter(|d| if is_input { d.is_input } else { d.is_output })
+        .find(|d| generate_device_id(&d.name, is_input) == device_id)
+        .map(|d| d.id.clone())
+        .or_else(|| match_device_name(name, Some(is_input), devices))
+}
+
+/// 与 `match_device_id` 相同，但 id 无法匹配时再用另一台机器上记录的设备名称匹配。
+/// 用于导入其他机器导出的配置：后端 id 因机器而异，设备名称通常相同
+pub(crate) fn match_device(
+    device_id: &str,
+    name: Option<&str>,
+    devices: &[DeviceInfo],
+) -> Option<String> {
+    match_device_id(device_id, devices)
+        .or_else(|| match_device_name(name?, device_direction(device_id), devices))
+}
+
+/// 名称最相似且相似度足够的设备；`is_input` 为 None 时不限方向
+fn match_device_name(name: &str, is_input: Option<bool>, devices: &[DeviceInfo]) -> Option<String> {
+    let mut best: Option<(&DeviceInfo, f64)> = None;
+    for device in devices.iter().filter(|d| match is_input {
+        Some(true) => d.is_input,
+        Some(false) => d.is_output,
+        None => true,
+    }) {
+        let score = name_similarity(name, &device.name);
+        if score >= MIN_NAME_SIMILARITY && best.is_none_or(|(_, s)| score > s) {
+            best = Some((device, score));
+        }
+    }
+    best.map(|(device, _)| device.id.clone())
+}
+
+/// id 表示的方向，兼容新旧两种格式
+fn device_direction(device_id: &str) -> Option<bool> {
+    if device_id.starts_with("input:") {
+        Some(true)
+    } else if device_id.starts_with("output:") {
+        Some(false)
+    } else {
+        parse_legacy_device_id(device_id).map(|(is_input, _)| is_input)
+    }
+}
+
+/// 从旧版 `{名称}_input` / `{名称}_output` 格式的 id 中取出方向和名称
+fn parse_legacy_device_id(device_id: &str) -> Option<(bool, &str)> {
+    if device_id.starts_with("input:") || device_id.starts_with("output:") {
+        return None;
+    }
+    if let Some(name) = device_id.strip_suffix("_input") {
+        return Some((true, name));
+    }
+    device_id.strip_suffix("_output").map(|name| (false, name))
+}
+
+/// 名称相似度（0 到 1），忽略大小写、空白和标点
+pub(crate) fn name_similarity(a: &str, b: &str) -> f64 {
+    let simplify = |s: &str| -> Vec<char> {
+        normalize_device_name(s)
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (simplify(a), simplify(b));
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// 旧版 id 格式，仅用于兼容已保存的配置
+pub(crate) fn generate_device_id(name: &str, is_input: bool) -> String {
+    let normalized_name = normalize_device_name(name);
+    format!(
+        "{}_{}",
+        normalized_name,
+        if is_input { "input" } else { "output" }
+    )
+}
+
+pub(crate) fn normalize_device_name(name: &str) -> String {
+    // 在Windows上，WASAPI设备描述可能包含" via "后缀
+    // 例如："CABLE Output via Line Input"
+    // 我们只保留" via "之前的部分作为规范名称
+    if let Some(pos) = name.find(" via ") {
+        name[..pos].trim().to_string()
+    } else {
+        name.trim().to_string()
+    }
+}
+
+pub(crate) fn is_vb_cable(name: &str) -> bool {
+    name.contains("Cable") || name.contains("VB Audio Cable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::backend::{VirtualBackend, VirtualDevice};
+
+    fn device(id: &str, name: &str, is_input: bool) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            backend_id: None,
+            is_input,
+            is_output: !is_input,
+            sample_rate: 48000,
+            channels: 2,
+            is_vb_cable: false,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_devices_get_distinct_ids() {
+        let usb = |backend_id| IdCandidate {
+            name: "USB Audio CODEC",
+            backend_id,
+            is_input: true,
+        };
+        let ids = assign_device_ids(&[usb(None), usb(None), usb(Some("alsa:hw:CARD=CODEC_1"))]);
+        assert_eq!(
+            ids,
+            vec![
+                "input:USB Audio CODEC",
+                "input:USB Audio CODEC#2",
+                "input:alsa:hw:CARD=CODEC_1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_old_ids_resolve_to_current_devices() {
+        let devices = vec![
+            device("input:{guid-1}", "CABLE Output via Line Input", true),
+            device(
+                "output:{guid-2}",
+                "Speakers (Realtek High Definition Audio)",
+                false,
+            ),
+        ];
+
+        // 旧格式：去掉 " via " 后缀的名称
+        assert_eq!(
+            match_device_id("CABLE Output_input", &devices).as_deref(),
+            Some("input:{guid-1}")
+        );
+        // 旧格式的名称略有变化（驱动更新后）
+        assert_eq!(
+            match_device_id(
+                "Speakers (Realtek(R) High Definition Audio)_output",
+                &devices
+            )
+            .as_deref(),
+            Some("output:{guid-2}")
+        );
+        // 方向不同的设备不会被匹配
+        assert_eq!(match_device_id("CABLE Output_output", &devices), None);
+        assert_eq!(match_device_id("output:Headphones", &devices), None);
+    }
+
+    #[test]
+    fn test_missing_duplicate_is_not_mapped_onto_its_sibling() {
+        // 两个相同的声卡中 `#2` 被拔掉，剩下的同名设备不能接管它的路由
+        let devices = vec![device("input:USB Audio CODEC", "USB Audio CODEC", true)];
+        assert_eq!(match_device_id("input:USB Audio CODEC#2", &devices), None);
+        let devices = vec![DeviceInfo {
+            backend_id: Some("hw:CODEC".into()),
+            ..device("input:hw:CODEC", "USB Audio CODEC", true)
+        }];
+        assert_eq!(match_device_id("input:hw:CODEC#2", &devices), None);
+        // 当前格式的 id 不做名称匹配
+        assert_eq!(match_device_id("input:USB Audio CODEC (2)", &devices), None);
+    }
+
+    #[test]
+    fn test_capabilities_summarize_supported_ranges() {
+        let range = |channels, min, max, format| SupportedConfigRange {
+            channels,
+            min_sample_rate: min,
+            max_sample_rate: max,
+            min_buffer_frames: Some(32),
+            max_buffer_frames: Some(2048),
+            sample_format: format,
+        };
+        let backend = VirtualBackend::new();
+        backend.add_device(
+            VirtualDevice::input("mic", 48000, 2).with_supported_configs(vec![
+                range(1, 8000, 48000, SampleFormat::I16),
+                range(2, 44100, 96000, SampleFormat::F32),
+            ]),
+        );
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+
+        let manager = DeviceManager::with_backend(Arc::new(backend));
+        let all = manager.list_capabilities().unwrap();
+        assert_eq!(all.len(), 2);
+
+        let mic = all.iter().find(|c| c.device_id == "mic").unwrap();
+        assert!(mic.is_input);
+        assert_eq!(
+            mic.sample_formats,
+            vec![SampleFormat::F32, SampleFormat::I16]
+        );
+        assert_eq!((mic.min_sample_rate, mic.max_sample_rate), (8000, 96000));
+        assert_eq!(mic.channel_counts, vec![1, 2]);
+        assert_eq!(
+            (mic.min_buffer_frames, mic.max_buffer_frames),
+            (Some(32), Some(2048))
+        );
+    }
+}
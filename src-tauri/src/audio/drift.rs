@@ -0,0 +1,72 @@
+/// 填充度平滑系数（每次回调）
+const FILL_SMOOTHING: f64 = 0.02;
+/// 比例项增益：填充度偏离目标 100% 时调整 0.5%
+const PROPORTIONAL_GAIN: f64 = 5e-3;
+/// 积分项增益，用于消除稳态误差（设备时钟固定的频偏）
+const INTEGRAL_GAIN: f64 = 2e-6;
+/// 最大调整幅度 ±0.5%，远大于实际声卡的时钟误差，同时音高变化不可闻
+pub(crate) const MAX_ADJUST: f64 = 5e-3;
+/// 填充度偏离目标在此范围内时不做调整，同一时钟的设备之间不需要重采样
+const DEADBAND: f64 = 0.1;
+/// 积分项低于此值（约 100 ppm）且填充度回到死区一半以内时停止调整
+const INTEGRAL_DEADBAND: f64 = 1e-4;
+
+/// 时钟漂移补偿控制器。
+///
+/// 根据路由环形缓冲区的填充度与目标延迟之间的误差，输出一个接近 1.0
+/// 的重采样比例系数：缓冲区偏满时稍微加快消费，偏空时稍微放慢，
+/// 使延迟长期稳定在目标附近。填充度在死区内时比例固定为 1.0。
+pub struct DriftController {
+    target_frames: f64,
+    smoothed_fill: f64,
+    integral: f64,
+    correcting: bool,
+}
+
+impl DriftController {
+    pub fn new(target_frames: f64) -> Self {
+        let target_frames = target_frames.max(1.0);
+        Self {
+            target_frames,
+            smoothed_fill: target_frames,
+            integral: 0.0,
+            correcting: false,
+        }
+    }
+
+    pub fn target_frames(&self) -> f64 {
+        self.target_frames
+    }
+
+    /// 当前是否在调整比例；为 false 时 update 返回 1.0
+    pub fn is_correcting(&self) -> bool {
+        self.correcting
+    }
+
+    /// 输入当前缓冲区填充帧数，返回比例系数
+    pub fn update(&mut self, fill_frames: usize) -> f64 {
+        self.smoothed_fill += FILL_SMOOTHING * (fill_frames as f64 - self.smoothed_fill);
+
+        let error = (self.smoothed_fill - self.target_frames) / self.target_frames;
+        // 带回差的死区：偏离超过死区才开始调整，回到一半以内且没有明显的固定频偏时停止
+        if self.correcting {
+            self.correcting =
+                error.abs() >= DEADBAND / 2.0 || self.integral.abs() >= INTEGRAL_DEADBAND;
+        } else {
+            self.correcting = error.abs() > DEADBAND;
+        }
+        if !self.correcting {
+            return 1.0;
+        }
+
+        self.integral = (self.integral + error * INTEGRAL_GAIN).clamp(-MAX_ADJUST, MAX_ADJUST);
+
+        1.0 + (error * PROPORTIONAL_GAIN + self.integral).clamp(-MAX_ADJUST, MAX_ADJUST)
+    }
+
+    pub fn reset(&mut self) {
+        self.smoothed_fill = self.target_frames;
+        self.integral = 0.0;
+        self.correcting = false;
+    }
+}
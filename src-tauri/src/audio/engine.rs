@@ -1,130 +1,624 @@
-use super::{device::DeviceManager, error::AudioError, mixer::AudioMixer};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use crossbeam::queue::SegQueue;
+use super::{
+    backend::StreamConfig,
+    backend::{AudioBackend, BackendStream},
+    channels::ChannelMap,
+    device::{DeviceConfig, DeviceManager},
+    drift::DriftController,
+    error::AudioError,
+    fanout::InputFanout,
+    graph::{OutputBus, RouteNode, RouteSource, MAX_CALLBACK_FRAMES},
+    limiter::LimiterSettings,
+    mixer::MixMode,
+    recovery::{DeviceFailure, Failure, StreamHealth, DEFAULT_RETRY_DELAY},
+    resampler::{ResampleQuality, Resampler},
+    ring_buffer::RingBuffer,
+};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{
         atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// 默认目标延迟（路由缓冲区的稳态填充量）
+pub const DEFAULT_TARGET_LATENCY_MS: f32 = 30.0;
+/// 默认的增益斜坡和淡入淡出时间
+pub const DEFAULT_RAMP_MS: f32 = 10.0;
+/// 路由缓冲区的最小容量（秒）
+const MIN_BUFFER_SECONDS: f32 = 1.0;
+
+type RouteKey = (String, String);
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Route {
     pub input_device_id: String,
     pub output_device_id: String,
     pub gain_db: f32,
     pub enabled: bool,
+    /// 输入输出采样率不一致时使用的重采样质量
+    #[serde(default)]
+    pub resample_quality: ResampleQuality,
+    /// 自定义声道映射；为空时按两端声道数使用默认映射
+    #[serde(default)]
+    pub channel_map: Option<ChannelMap>,
+}
+
+impl Route {
+    pub fn new(input_device_id: &str, output_device_id: &str) -> Self {
+        Self {
+            input_device_id: input_device_id.to_string(),
+            output_device_id: output_device_id.to_string(),
+            gain_db: 0.0,
+            enabled: true,
+            resample_quality: ResampleQuality::default(),
+            channel_map: None,
+        }
+    }
+}
+
+/// 引擎的运行状态
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineState {
+    #[default]
+    Stopped,
+    Starting,
+    /// 所有启用的路由都在运行
+    Running,
+    /// 正在运行，但部分设备无法打开或已出错，经过它们的路由暂停并等待重试
+    Degraded,
+    Stopping,
+}
+
+/// 引擎状态报告：当前状态以及每个出错设备的原因
+#[derive(Clone, Debug, Serialize)]
+pub struct EngineStatus {
+    pub state: EngineState,
+    #[serde(flatten)]
+    pub health: StreamHealth,
 }
 
 pub struct AudioEngine {
     pub device_manager: DeviceManager,
-    pub input_streams: HashMap<String, cpal::Stream>,
-    pub output_streams: HashMap<String, cpal::Stream>,
+    pub input_streams: HashMap<String, Box<dyn BackendStream>>,
+    pub output_streams: HashMap<String, Box<dyn BackendStream>>,
     pub routes: Vec<Route>,
     pub device_gains: HashMap<String, f32>,
     pub peak_levels: HashMap<String, Arc<AtomicU32>>,
 
+    state: EngineState,
+    /// 音频回调是否处理数据
     running: Arc<AtomicBool>,
-    mixer: Arc<parking_lot::Mutex<AudioMixer>>,
 
-    buffer_pool: Arc<SegQueue<Vec<f32>>>,
+    /// 每个输入设备一个广播器，向该输入的每条路由分发独立的缓冲区
+    input_fanouts: HashMap<String, Arc<InputFanout>>,
+    input_configs: HashMap<String, StreamConfig>,
+    /// 每个输出设备的路由列表，输出回调通过它读取当前的图
+    output_buses: HashMap<String, Arc<OutputBus>>,
+    output_configs: HashMap<String, StreamConfig>,
+    /// 每个输出设备的混合方式，未设置时使用默认值
+    mix_modes: HashMap<String, MixMode>,
+    limiters: HashMap<String, LimiterSettings>,
+    /// 每个设备的流参数覆盖
+    device_configs: HashMap<String, DeviceConfig>,
+    route_nodes: HashMap<RouteKey, Arc<RouteNode>>,
+    /// 已从图中移除、仍在淡出的节点
+    retiring: Vec<Arc<RouteNode>>,
+    /// 流错误回调报告的错误，由 recover_streams 处理
+    stream_errors: Arc<parking_lot::Mutex<HashMap<String, String>>>,
+    /// 无法打开或运行中出错、等待重试的设备
+    failures: HashMap<String, Failure>,
+    retry_delay: Duration,
 
-    input_queues: HashMap<String, Arc<SegQueue<Vec<f32>>>>,
+    target_latency_ms: f32,
+    ramp_ms: f32,
+    drift_compensation: bool,
 }
 
 impl AudioEngine {
     pub fn new() -> Self {
-        let buffer_pool = Arc::new(SegQueue::new());
+        Self::with_device_manager(DeviceManager::new())
+    }
 
-        for _ in 0..10 {
-            buffer_pool.push(Vec::with_capacity(512));
-        }
+    pub fn with_backend(backend: Arc<dyn AudioBackend>) -> Self {
+        Self::with_device_manager(DeviceManager::with_backend(backend))
+    }
 
+    fn with_device_manager(device_manager: DeviceManager) -> Self {
         Self {
-            device_manager: DeviceManager::new(),
+            device_manager,
             input_streams: HashMap::new(),
             output_streams: HashMap::new(),
             routes: Vec::new(),
             device_gains: HashMap::new(),
             peak_levels: HashMap::new(),
+            state: EngineState::Stopped,
             running: Arc::new(AtomicBool::new(false)),
-            mixer: Arc::new(parking_lot::Mutex::new(AudioMixer::new())),
-            buffer_pool,
-            input_queues: HashMap::new(),
+            input_fanouts: HashMap::new(),
+            input_configs: HashMap::new(),
+            output_buses: HashMap::new(),
+            output_configs: HashMap::new(),
+            mix_modes: HashMap::new(),
+            limiters: HashMap::new(),
+            device_configs: HashMap::new(),
+            route_nodes: HashMap::new(),
+            retiring: Vec::new(),
+            stream_errors: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            failures: HashMap::new(),
+            retry_delay: DEFAULT_RETRY_DELAY,
+            target_latency_ms: DEFAULT_TARGET_LATENCY_MS,
+            ramp_ms: DEFAULT_RAMP_MS,
+            drift_compensation: true,
         }
     }
 
-    pub fn start(&mut self) -> Result<(), AudioError> {
+    /// 启动引擎。能打开的设备都会启动，无法打开的设备记入状态报告并在后台重试，
+    /// 不会因为单个设备失败而中止；有设备失败时状态为 Degraded。
+    /// 一个流都无法打开时停止引擎并返回第一个错误
+    pub fn start(&mut self) -> Result<EngineStatus, AudioError> {
+        self.state = EngineState::Starting;
         self.running.store(true, Ordering::SeqCst);
 
-        let host = cpal::default_host();
+        // 已在运行时只补齐缺少的流，不会重复创建
+        if let Err(e) = self.apply_graph() {
+            if self.input_streams.is_empty() && self.output_streams.is_empty() {
+                tracing::error!("Audio engine failed to start: {}", e);
+                self.stop()?;
+                return Err(e);
+            }
+            tracing::warn!("Audio engine started with errors: {}", e);
+        }
+        self.update_state();
 
-        let mut input_device_ids: std::collections::HashSet<String> =
-            std::collections::HashSet::new();
-        let mut output_device_ids: std::collections::HashSet<String> =
-            std::collections::HashSet::new();
+        tracing::info!("Audio engine started: {:?}", self.state);
+        Ok(self.status())
+    }
 
-        for route in &self.routes {
-            if route.enabled {
-                input_device_ids.insert(route.input_device_id.clone());
-                output_device_ids.insert(route.output_device_id.clone());
+    /// 停止引擎并立即关闭全部流，丢弃路由缓冲区中尚未播放的数据；
+    /// 下次启动时重新打开设备
+    pub fn stop(&mut self) -> Result<(), AudioError> {
+        self.state = EngineState::Stopping;
+
+        self.running.store(false, Ordering::SeqCst);
+        self.release_streams();
+
+        self.state = EngineState::Stopped;
+        tracing::info!("Audio engine stopped");
+        Ok(())
+    }
+
+    /// 以当前的路由和设置重建整个音频图：释放所有设备后重新打开。
+    /// 调用方持有引擎锁，其他命令不会看到中间状态
+    pub fn restart(&mut self) -> Result<EngineStatus, AudioError> {
+        tracing::info!("Restarting audio engine");
+        self.stop()?;
+        self.start()
+    }
+
+    /// 关闭所有设备流，丢弃所有节点、缓冲区和出错记录
+    fn release_streams(&mut self) {
+        for stream in self
+            .input_streams
+            .values()
+            .chain(self.output_streams.values())
+        {
+            let _ = stream.pause();
+        }
+        for bus in self.output_buses.values() {
+            bus.set_routes(Vec::new());
+        }
+
+        self.route_nodes.clear();
+        self.retiring.clear();
+        self.input_streams.clear();
+        self.input_fanouts.clear();
+        self.input_configs.clear();
+        self.output_streams.clear();
+        self.output_buses.clear();
+        self.output_configs.clear();
+        self.peak_levels.clear();
+        self.stream_errors.lock().clear();
+        self.failures.clear();
+    }
+
+    pub fn state(&self) -> EngineState {
+        self.state
+    }
+
+    pub fn status(&self) -> EngineStatus {
+        EngineStatus {
+            state: self.state,
+            health: self.stream_health(),
+        }
+    }
+
+    /// 运行中按设备故障情况在 Running 和 Degraded 之间切换
+    fn update_state(&mut self) {
+        if !matches!(
+            self.state,
+            EngineState::Starting | EngineState::Running | EngineState::Degraded
+        ) {
+            return;
+        }
+        let state = if self.failures.is_empty() {
+            EngineState::Running
+        } else {
+            EngineState::Degraded
+        };
+        if state != self.state && self.state != EngineState::Starting {
+            tracing::info!("Audio engine state: {:?} -> {:?}", self.state, state);
+        }
+        self.state = state;
+    }
+
+    /// 设置路由缓冲区的目标延迟，对之后新建的路由生效
+    pub fn set_target_latency_ms(&mut self, target_ms: f32) {
+        self.target_latency_ms = target_ms.max(1.0);
+    }
+
+    pub fn target_latency_ms(&self) -> f32 {
+        self.target_latency_ms
+    }
+
+    /// 设置增益变化和路由淡入淡出的斜坡时间，0 表示立即生效
+    pub fn set_ramp_ms(&mut self, ramp_ms: f32) {
+        self.ramp_ms = ramp_ms.max(0.0);
+        for (device_id, bus) in &self.output_buses {
+            if let Some(config) = self.output_configs.get(device_id) {
+                bus.set_ramp_frames(self.ramp_frames(config));
             }
         }
+    }
 
-        for device_id in input_device_ids {
-            self.create_input_stream(&host, &device_id)?;
+    pub fn ramp_ms(&self) -> f32 {
+        self.ramp_ms
+    }
+
+    fn ramp_frames(&self, config: &StreamConfig) -> usize {
+        (self.ramp_ms / 1000.0 * config.sample_rate as f32) as usize
+    }
+
+    /// 开关时钟漂移补偿。关闭后采样率相同的路由直接透传，不做重采样，
+    /// 适用于输入输出共享同一时钟的场景（例如离线渲染）
+    pub fn set_drift_compensation(&mut self, enabled: bool) {
+        self.drift_compensation = enabled;
+    }
+
+    /// 设置设备出错后第一次重试的等待时间，之后每次失败加倍
+    pub fn set_retry_delay(&mut self, delay: Duration) {
+        self.retry_delay = delay;
+    }
+
+    /// 处理流错误回调报告的故障，并重试到期的设备。
+    /// 出错设备的流被关闭，经过它的路由处于降级状态，直到设备重新打开；
+    /// 其他路由不受影响；同时移除已经淡出完成的节点。返回健康状态是否发生变化
+    pub fn recover_streams(&mut self) -> bool {
+        let errors: Vec<(String, String)> = self.stream_errors.lock().drain().collect();
+        let before: HashSet<String> = self.failures.keys().cloned().collect();
+
+        let mut closed = false;
+        for (device_id, error) in errors {
+            // 已经关闭的流的迟到错误
+            if !self.input_streams.contains_key(&device_id)
+                && !self.output_streams.contains_key(&device_id)
+            {
+                continue;
+            }
+            tracing::warn!("Stream for device {} failed: {}", device_id, error);
+            self.close_device(&device_id);
+            self.record_failure(&device_id, error);
+            closed = true;
         }
 
-        for device_id in output_device_ids {
-            self.create_output_stream(&host, &device_id)?;
+        if closed || self.failures.values().any(Failure::is_due) {
+            if let Err(e) = self.apply_graph() {
+                tracing::debug!("Recovery attempt incomplete: {}", e);
+            }
         }
 
-        tracing::info!("Audio engine started");
-        Ok(())
+        // 移除上次以来已经淡出完成的节点
+        if self.retiring.iter().any(|node| node.is_finished()) {
+            self.publish_graph();
+        }
+
+        closed || self.failures.keys().cloned().collect::<HashSet<_>>() != before
     }
 
-    pub fn stop(&mut self) -> Result<(), AudioError> {
-        self.running.store(false, Ordering::SeqCst);
+    /// 出错的设备和因此降级的路由
+    pub fn stream_health(&self) -> StreamHealth {
+        let now = Instant::now();
+        let mut failed_devices: Vec<DeviceFailure> = self
+            .failures
+            .iter()
+            .map(|(device_id, failure)| DeviceFailure {
+                device_id: device_id.clone(),
+                error: failure.error.clone(),
+                attempts: failure.attempts,
+                retry_in_ms: failure
+                    .next_retry
+                    .saturating_duration_since(now)
+                    .as_millis() as u64,
+            })
+            .collect();
+        failed_devices.sort_by(|a, b| a.device_id.cmp(&b.device_id));
 
-        for stream in self.input_streams.values() {
-            let _ = stream.pause();
+        let degraded_routes = self
+            .routes
+            .iter()
+            .filter(|r| {
+                r.enabled
+                    && (self.failures.contains_key(&r.input_device_id)
+                        || self.failures.contains_key(&r.output_device_id))
+            })
+            .cloned()
+            .collect();
+
+        StreamHealth {
+            failed_devices,
+            degraded_routes,
         }
-        for stream in self.output_streams.values() {
-            let _ = stream.pause();
+    }
+
+    fn record_failure(&mut self, device_id: &str, error: String) {
+        let failure = Failure::record(self.failures.get(device_id), error, self.retry_delay);
+        tracing::info!(
+            "Retrying device {} in {} ms (attempt {})",
+            device_id,
+            failure
+                .next_retry
+                .saturating_duration_since(Instant::now())
+                .as_millis(),
+            failure.attempts
+        );
+        self.failures.insert(device_id.to_string(), failure);
+    }
+
+    /// 让运行中的音频图与 `routes` 保持一致：按需打开或关闭设备流，
+    /// 为新路由建立节点，然后原子替换每个输出的路由列表。
+    /// 输出回调在下一个缓冲区就会看到新的图。
+    fn apply_graph(&mut self) -> Result<(), AudioError> {
+        if !self.is_running() {
+            return Ok(());
         }
+        self.resolve_device_ids();
 
-        tracing::info!("Audio engine stopped");
-        Ok(())
+        let mut first_error = None;
+        let active: Vec<Route> = self.routes.iter().filter(|r| r.enabled).cloned().collect();
+
+        // 不再被任何启用路由使用的设备不再重试
+        self.failures.retain(|device_id, _| {
+            active
+                .iter()
+                .any(|r| &r.input_device_id == device_id || &r.output_device_id == device_id)
+        });
+
+        let mut attempted = HashSet::new();
+        for route in &active {
+            for (device_id, is_input) in [
+                (&route.input_device_id, true),
+                (&route.output_device_id, false),
+            ] {
+                let open = if is_input {
+                    self.input_streams.contains_key(device_id)
+                } else {
+                    self.output_streams.contains_key(device_id)
+                };
+                // 出错的设备在退避时间到达之前不重试
+                if open
+                    || attempted.contains(device_id)
+                    || self.failures.get(device_id).is_some_and(|f| !f.is_due())
+                {
+                    continue;
+                }
+                attempted.insert(device_id.clone());
+
+                let result = if is_input {
+                    self.create_input_stream(device_id)
+                } else {
+                    self.create_output_stream(device_id)
+                };
+                match result {
+                    Ok(()) => {
+                        if self.failures.remove(device_id).is_some() {
+                            tracing::info!("Device {} recovered", device_id);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to open {} device {}: {}",
+                            if is_input { "input" } else { "output" },
+                            device_id,
+                            e
+                        );
+                        self.record_failure(device_id, e.to_string());
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+
+        let mut nodes: HashMap<RouteKey, Arc<RouteNode>> = HashMap::new();
+        for route in &active {
+            let key = route_key(route);
+            let (Some(fanout), Some(input_config), Some(output_config)) = (
+                self.input_fanouts.get(&route.input_device_id),
+                self.input_configs.get(&route.input_device_id),
+                self.output_configs.get(&route.output_device_id),
+            ) else {
+                continue;
+            };
+
+            let node = match self.route_nodes.get(&key) {
+                Some(node) if node.is_compatible(route) => {
+                    node.set_gain_db(route.gain_db);
+                    Arc::clone(node)
+                }
+                _ => {
+                    // 重建节点时使用新的消费者缓冲区；旧节点保留自己的缓冲区继续淡出，
+                    // 与新节点交叉淡化
+                    let buffer = match self.route_nodes.get(&key) {
+                        Some(_) => fanout.replace(&route.output_device_id),
+                        None => fanout.subscribe(&route.output_device_id),
+                    };
+                    let source =
+                        self.build_route_source(route, buffer, input_config, output_config);
+                    Arc::new(RouteNode::new(route.clone(), source))
+                }
+            };
+            nodes.insert(key, node);
+        }
+
+        for (key, node) in std::mem::take(&mut self.route_nodes) {
+            if nodes.get(&key).is_some_and(|n| Arc::ptr_eq(n, &node)) {
+                continue;
+            }
+            node.fade_out();
+            self.retiring.push(node);
+        }
+        self.route_nodes = nodes;
+
+        self.publish_graph();
+        self.update_state();
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
-    fn create_input_stream(
-        &mut self,
-        host: &cpal::Host,
-        device_id: &str,
-    ) -> Result<(), AudioError> {
-        let device = self.find_input_device_by_id(host, device_id)?;
-        let config = device.default_input_config()?;
-        let stream_config = config.config();
+    /// 把旧配置中的设备 id 映射到当前设备，路由和按设备保存的设置一起改名。
+    /// 每次打开设备流之前调用：设备在启动时不在，之后插入时由恢复流程完成映射
+    fn resolve_device_ids(&mut self) {
+        let device_ids: HashSet<String> = self
+            .routes
+            .iter()
+            .flat_map(|r| [r.input_device_id.clone(), r.output_device_id.clone()])
+            .chain(self.device_gains.keys().cloned())
+            .chain(self.mix_modes.keys().cloned())
+            .chain(self.limiters.keys().cloned())
+            .chain(self.device_configs.keys().cloned())
+            .collect();
+        let renames = self.device_manager.resolve_device_ids(&device_ids);
+        if renames.is_empty() {
+            return;
+        }
+
+        for route in &mut self.routes {
+            for id in [&mut route.input_device_id, &mut route.output_device_id] {
+                if let Some(resolved) = renames.get(id) {
+                    *id = resolved.clone();
+                }
+            }
+        }
+        // 改名后与已有路由重复时保留先出现的
+        let mut seen = HashSet::new();
+        self.routes.retain(|r| seen.insert(route_key(r)));
 
-        let queue = Arc::new(SegQueue::new());
-        self.input_queues
-            .insert(device_id.to_string(), Arc::clone(&queue));
+        rename_keys(&mut self.device_gains, &renames);
+        rename_keys(&mut self.mix_modes, &renames);
+        rename_keys(&mut self.limiters, &renames);
+        rename_keys(&mut self.device_configs, &renames);
+    }
+
+    /// 移除淡出完成的节点，替换每个输出的路由列表，并关闭不再使用的设备流。
+    /// 仍在淡出的节点留在图中，由下一次 apply_graph 或 recover_streams 移除
+    fn publish_graph(&mut self) {
+        let ramp_ms = self.ramp_ms;
+        let input_fanouts = &self.input_fanouts;
+        self.retiring.retain(|node| {
+            // 输出流已关闭或不需要淡出时直接移除
+            let done = node.is_finished() || ramp_ms == 0.0;
+            if done {
+                if let Some(fanout) = input_fanouts.get(&node.route.input_device_id) {
+                    fanout.release(&node.buffer);
+                }
+            }
+            !done
+        });
 
+        for (output_id, bus) in &self.output_buses {
+            let mut routes: Vec<Arc<RouteNode>> = self
+                .route_nodes
+                .iter()
+                .filter(|((_, o), _)| o == output_id)
+                .map(|(_, node)| Arc::clone(node))
+                .collect();
+            routes.sort_by(|a, b| a.route.input_device_id.cmp(&b.route.input_device_id));
+            routes.extend(
+                self.retiring
+                    .iter()
+                    .filter(|node| &node.route.output_device_id == output_id)
+                    .cloned(),
+            );
+            bus.set_routes(routes);
+        }
+
+        self.close_unused_streams();
+    }
+
+    /// 关闭不再被任何启用路由使用的设备流；仍有节点在淡出的设备保持打开
+    fn close_unused_streams(&mut self) {
+        let used_inputs: HashSet<&String> = self
+            .route_nodes
+            .keys()
+            .map(|(i, _)| i)
+            .chain(self.retiring.iter().map(|n| &n.route.input_device_id))
+            .collect();
+        let used_outputs: HashSet<&String> = self
+            .route_nodes
+            .keys()
+            .map(|(_, o)| o)
+            .chain(self.retiring.iter().map(|n| &n.route.output_device_id))
+            .collect();
+
+        let unused_inputs: Vec<String> = self
+            .input_streams
+            .keys()
+            .filter(|id| !used_inputs.contains(id))
+            .cloned()
+            .collect();
+        let unused_outputs: Vec<String> = self
+            .output_streams
+            .keys()
+            .filter(|id| !used_outputs.contains(id))
+            .cloned()
+            .collect();
+
+        for device_id in unused_inputs {
+            self.input_streams.remove(&device_id);
+            self.input_fanouts.remove(&device_id);
+            self.input_configs.remove(&device_id);
+            self.peak_levels.remove(&device_id);
+            tracing::info!("Closed input stream for device: {}", device_id);
+        }
+        for device_id in unused_outputs {
+            self.output_streams.remove(&device_id);
+            self.output_buses.remove(&device_id);
+            self.output_configs.remove(&device_id);
+            tracing::info!("Closed output stream for device: {}", device_id);
+        }
+    }
+
+    fn create_input_stream(&mut self, device_id: &str) -> Result<(), AudioError> {
+        let backend = Arc::clone(self.device_manager.backend());
+        let stream_config = self.stream_config(device_id, true)?;
+
+        let fanout = Arc::new(InputFanout::new(self.route_buffer_capacity(&stream_config)));
         let peak_detector = Arc::new(AtomicU32::new(0));
-        self.peak_levels
-            .insert(device_id.to_string(), Arc::clone(&peak_detector));
 
-        let queue_clone = Arc::clone(&queue);
-        let pool_clone = Arc::clone(&self.buffer_pool);
+        let fanout_clone = Arc::clone(&fanout);
         let peak_clone = Arc::clone(&peak_detector);
         let running_clone = Arc::clone(&self.running);
         let device_id_clone = device_id.to_string();
+        let errors_clone = Arc::clone(&self.stream_errors);
 
-        let stream = device.build_input_stream(
+        let stream = backend.open_input_stream(
+            device_id,
             &stream_config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+            Box::new(move |data: &[f32]| {
                 if !running_clone.load(Ordering::SeqCst) {
                     return;
                 }
@@ -132,176 +626,190 @@ impl AudioEngine {
                 let peak = data.iter().fold(0.0f32, |max, &s| max.max(s.abs()));
                 peak_clone.store((peak * 1000.0) as u32, Ordering::SeqCst);
 
-                let mut audio_buffer = pool_clone
-                    .pop()
-                    .unwrap_or_else(|| Vec::with_capacity(data.len()));
-
-                audio_buffer.clear();
-                audio_buffer.extend_from_slice(data);
-
-                queue_clone.push(audio_buffer);
-            },
-            move |err| {
+                fanout_clone.broadcast(data);
+            }),
+            Box::new(move |err| {
                 tracing::error!("Input stream error for device {}: {}", device_id_clone, err);
-            },
-            None,
+                errors_clone.lock().insert(device_id_clone.clone(), err);
+            }),
         )?;
 
         stream.play()?;
         self.input_streams.insert(device_id.to_string(), stream);
+        self.input_fanouts.insert(device_id.to_string(), fanout);
+        self.input_configs
+            .insert(device_id.to_string(), stream_config);
+        self.peak_levels
+            .insert(device_id.to_string(), peak_detector);
 
         tracing::info!("Created input stream for device: {}", device_id);
         Ok(())
     }
 
-    fn create_output_stream(
-        &mut self,
-        host: &cpal::Host,
-        device_id: &str,
-    ) -> Result<(), AudioError> {
-        let device = self.find_output_device_by_id(host, device_id)?;
-        let config = device.default_output_config()?;
-        let stream_config = config.config();
+    fn stream_config(&self, device_id: &str, is_input: bool) -> Result<StreamConfig, AudioError> {
+        let overrides = self
+            .device_configs
+            .get(device_id)
+            .copied()
+            .unwrap_or_default();
+        self.device_manager
+            .resolve_stream_config(device_id, is_input, &overrides)
+    }
 
-        let routes_for_output: Vec<Route> = self
-            .routes
-            .iter()
-            .filter(|r| r.output_device_id == device_id && r.enabled)
-            .cloned()
-            .collect();
+    fn route_buffer_capacity(&self, config: &StreamConfig) -> usize {
+        let seconds = (self.target_latency_ms / 1000.0 * 4.0).max(MIN_BUFFER_SECONDS);
+        (config.sample_rate as f32 * seconds) as usize * config.channels as usize
+    }
 
-        let mut input_sources: Vec<(Arc<SegQueue<Vec<f32>>>, f32)> = Vec::new();
+    fn create_output_stream(&mut self, device_id: &str) -> Result<(), AudioError> {
+        let backend = Arc::clone(self.device_manager.backend());
+        let stream_config = self.stream_config(device_id, false)?;
 
-        for route in &routes_for_output {
-            if let Some(queue) = self.input_queues.get(&route.input_device_id) {
-                let gain_linear = 10.0_f32.powf(route.gain_db / 20.0);
-                input_sources.push((Arc::clone(queue), gain_linear));
-            }
-        }
+        let bus = Arc::new(OutputBus::new(
+            &stream_config,
+            self.mix_mode(device_id),
+            self.limiter_settings(device_id),
+            self.ramp_frames(&stream_config),
+        ));
 
+        let bus_clone = Arc::clone(&bus);
         let running_clone = Arc::clone(&self.running);
-        let pool_clone = Arc::clone(&self.buffer_pool);
         let device_id_clone = device_id.to_string();
+        let errors_clone = Arc::clone(&self.stream_errors);
+        let mut scratch = bus.scratch();
 
-        let stream = device.build_output_stream(
+        let stream = backend.open_output_stream(
+            device_id,
             &stream_config,
-            move |output: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            Box::new(move |output: &mut [f32]| {
                 if !running_clone.load(Ordering::SeqCst) {
                     return;
                 }
 
-                let mut mix_buffer = vec![0.0f32; output.len()];
-                let mut total_samples = 0;
-
-                for (queue, gain) in &input_sources {
-                    if let Some(audio) = queue.pop() {
-                        total_samples += audio.len();
-
-                        for (i, &sample) in audio.iter().enumerate() {
-                            if i < mix_buffer.len() {
-                                mix_buffer[i] += sample * gain;
-                            }
-                        }
-
-                        pool_clone.push(audio);
-                    }
-                }
-
-                if total_samples > 0 {
-                    let num_inputs = input_sources.len().max(1) as f32;
-                    for sample in &mut mix_buffer {
-                        *sample = (*sample / num_inputs).clamp(-1.0, 1.0);
-                    }
-                }
-
-                output.copy_from_slice(&mix_buffer);
-            },
-            move |err| {
+                bus_clone.render(output, &mut scratch);
+            }),
+            Box::new(move |err| {
                 tracing::error!(
                     "Output stream error for device {}: {}",
                     device_id_clone,
                     err
                 );
-            },
-            None,
+                errors_clone.lock().insert(device_id_clone.clone(), err);
+            }),
         )?;
 
         stream.play()?;
         self.output_streams.insert(device_id.to_string(), stream);
+        self.output_buses.insert(device_id.to_string(), bus);
+        self.output_configs
+            .insert(device_id.to_string(), stream_config);
 
         tracing::info!("Created output stream for device: {}", device_id);
         Ok(())
     }
 
-    fn normalize_device_name(&self, name: &str) -> String {
-        // 与device.rs中的normalize_device_name保持一致
-        // 移除" via "后缀
-        if let Some(pos) = name.find(" via ") {
-            name[..pos].trim().to_string()
-        } else {
-            name.trim().to_string()
-        }
-    }
-
-    fn find_input_device_by_id(
+    fn build_route_source(
         &self,
-        host: &cpal::Host,
-        device_id: &str,
-    ) -> Result<cpal::Device, AudioError> {
-        for device in host
-            .input_devices()
-            .map_err(|e| AudioError::Device(e.to_string()))?
-        {
-            let name = device
-                .description()
-                .map_err(|e| AudioError::Device(e.to_string()))?
-                .to_string();
-            let normalized_name = self.normalize_device_name(&name);
-            let generated_id = format!("{}_input", normalized_name);
-            if generated_id == device_id {
-                return Ok(device);
-            }
-        }
-        Err(AudioError::DeviceNotFound(device_id.to_string()))
-    }
+        route: &Route,
+        buffer: Arc<RingBuffer>,
+        input_config: &StreamConfig,
+        output_config: &StreamConfig,
+    ) -> RouteSource {
+        let channel_map = resolve_channel_map(route, input_config.channels, output_config.channels);
+        let nominal_ratio = input_config.sample_rate as f64 / output_config.sample_rate as f64;
 
-    fn find_output_device_by_id(
-        &self,
-        host: &cpal::Host,
-        device_id: &str,
-    ) -> Result<cpal::Device, AudioError> {
-        for device in host
-            .output_devices()
-            .map_err(|e| AudioError::Device(e.to_string()))?
-        {
-            let name = device
-                .description()
-                .map_err(|e| AudioError::Device(e.to_string()))?
-                .to_string();
-            let normalized_name = self.normalize_device_name(&name);
-            let generated_id = format!("{}_output", normalized_name);
-            if generated_id == device_id {
-                return Ok(device);
-            }
-        }
-        Err(AudioError::DeviceNotFound(device_id.to_string()))
+        // 采样率不一致时自动插入重采样器；开启漂移补偿时所有路由都需要可调比例的重采样器，
+        // 采样率相同的路由只在漂移超出死区时才实际重采样
+        let resampler = (input_config.sample_rate != output_config.sample_rate
+            || self.drift_compensation)
+            .then(|| {
+                tracing::info!(
+                    "Resampling route {} -> {}: {} Hz -> {} Hz ({:?})",
+                    route.input_device_id,
+                    route.output_device_id,
+                    input_config.sample_rate,
+                    output_config.sample_rate,
+                    route.resample_quality
+                );
+                Resampler::new(
+                    route.resample_quality,
+                    input_config.sample_rate,
+                    output_config.sample_rate,
+                    output_config.channels,
+                )
+            });
+
+        let drift = self.drift_compensation.then(|| {
+            DriftController::new(
+                self.target_latency_ms as f64 / 1000.0 * input_config.sample_rate as f64,
+            )
+        });
+
+        RouteSource::new(
+            buffer,
+            input_config.channels as usize,
+            (!channel_map.is_identity()).then_some(channel_map),
+            resampler,
+            nominal_ratio,
+            drift,
+            MAX_CALLBACK_FRAMES,
+        )
     }
 
     pub fn add_route(&mut self, route: Route) -> Result<(), AudioError> {
+        // 同一对输入输出只保留一条路由，重复添加视为更新
+        self.routes.retain(|r| {
+            !(r.input_device_id == route.input_device_id
+                && r.output_device_id == route.output_device_id)
+        });
         self.routes.push(route.clone());
         tracing::info!(
             "Added route: {} -> {}",
             route.input_device_id,
             route.output_device_id
         );
-        Ok(())
+        self.apply_graph()
+    }
+
+    /// 整体替换路由（加载配置时使用）。运行中只重建一次图，
+    /// 保留的路由不受影响，新增和移除的路由交叉淡化
+    pub fn set_routes(&mut self, routes: Vec<Route>) -> Result<(), AudioError> {
+        self.routes.clear();
+        for route in routes {
+            self.routes.retain(|r| route_key(r) != route_key(&route));
+            self.routes.push(route);
+        }
+        tracing::info!("Replaced routes: {} routes", self.routes.len());
+        self.apply_graph()
     }
 
     pub fn remove_route(&mut self, input_id: &str, output_id: &str) -> Result<(), AudioError> {
         self.routes
             .retain(|r| !(r.input_device_id == input_id && r.output_device_id == output_id));
         tracing::info!("Removed route: {} -> {}", input_id, output_id);
-        Ok(())
+        self.apply_graph()
+    }
+
+    pub fn set_route_enabled(
+        &mut self,
+        input_id: &str,
+        output_id: &str,
+        enabled: bool,
+    ) -> Result<(), AudioError> {
+        let route = self
+            .routes
+            .iter_mut()
+            .find(|r| r.input_device_id == input_id && r.output_device_id == output_id)
+            .ok_or_else(|| AudioError::Config(format!("No route {} -> {}", input_id, output_id)))?;
+        route.enabled = enabled;
+
+        tracing::info!(
+            "{} route: {} -> {}",
+            if enabled { "Enabled" } else { "Disabled" },
+            input_id,
+            output_id
+        );
+        self.apply_graph()
     }
 
     pub fn set_gain(&mut self, device_id: &str, gain_db: f32) -> Result<(), AudioError> {
@@ -310,6 +818,10 @@ impl AudioEngine {
         for route in &mut self.routes {
             if route.input_device_id == device_id {
                 route.gain_db = gain_db;
+                // 增益直接写入运行中的节点，无需重建图
+                if let Some(node) = self.route_nodes.get(&route_key(route)) {
+                    node.set_gain_db(gain_db);
+                }
             }
         }
 
@@ -317,6 +829,179 @@ impl AudioEngine {
         Ok(())
     }
 
+    pub fn set_mix_mode(&mut self, device_id: &str, mode: MixMode) -> Result<(), AudioError> {
+        mode.validate()?;
+        self.mix_modes.insert(device_id.to_string(), mode);
+        if let Some(bus) = self.output_buses.get(device_id) {
+            bus.set_mix_mode(mode);
+        }
+        tracing::info!("Set mix mode for device {}: {:?}", device_id, mode);
+        Ok(())
+    }
+
+    /// 整体替换各输出的混合方式，未列出的设备恢复默认值
+    pub fn set_mix_modes(&mut self, modes: HashMap<String, MixMode>) -> Result<(), AudioError> {
+        for mode in modes.values() {
+            mode.validate()?;
+        }
+        self.mix_modes = modes;
+        for (device_id, bus) in &self.output_buses {
+            bus.set_mix_mode(self.mix_mode(device_id));
+        }
+        Ok(())
+    }
+
+    pub fn mix_mode(&self, device_id: &str) -> MixMode {
+        self.mix_modes.get(device_id).copied().unwrap_or_default()
+    }
+
+    pub fn get_mix_modes(&self) -> HashMap<String, MixMode> {
+        self.mix_modes.clone()
+    }
+
+    pub fn set_limiter_settings(
+        &mut self,
+        device_id: &str,
+        settings: LimiterSettings,
+    ) -> Result<(), AudioError> {
+        settings.validate()?;
+        self.limiters.insert(device_id.to_string(), settings);
+        if let Some(bus) = self.output_buses.get(device_id) {
+            bus.set_limiter(settings);
+        }
+        tracing::info!("Set limiter for device {}: {:?}", device_id, settings);
+        Ok(())
+    }
+
+    /// 整体替换各输出的限制器参数，未列出的设备恢复默认值
+    pub fn set_limiters(
+        &mut self,
+        limiters: HashMap<String, LimiterSettings>,
+    ) -> Result<(), AudioError> {
+        for settings in limiters.values() {
+            settings.validate()?;
+        }
+        self.limiters = limiters;
+        for (device_id, bus) in &self.output_buses {
+            bus.set_limiter(self.limiter_settings(device_id));
+        }
+        Ok(())
+    }
+
+    pub fn limiter_settings(&self, device_id: &str) -> LimiterSettings {
+        self.limiters.get(device_id).copied().unwrap_or_default()
+    }
+
+    pub fn get_limiter_settings(&self) -> HashMap<String, LimiterSettings> {
+        self.limiters.clone()
+    }
+
+    /// 每个运行中的输出设备最近一个缓冲区的限制器增益衰减（dB）
+    pub fn get_gain_reduction(&self) -> HashMap<String, f32> {
+        self.output_buses
+            .iter()
+            .map(|(id, bus)| (id.clone(), bus.gain_reduction_db()))
+            .collect()
+    }
+
+    /// 设置设备的流参数覆盖。参数先按设备支持的配置校验；
+    /// 设备正在使用时立即以新参数重新打开
+    pub fn set_device_config(
+        &mut self,
+        device_id: &str,
+        config: DeviceConfig,
+    ) -> Result<(), AudioError> {
+        for capabilities in self.device_manager.capabilities(device_id)? {
+            self.device_manager
+                .resolve_stream_config(device_id, capabilities.is_input, &config)?;
+        }
+
+        if config.is_empty() {
+            self.device_configs.remove(device_id);
+        } else {
+            self.device_configs.insert(device_id.to_string(), config);
+        }
+        tracing::info!("Set stream config for device {}: {:?}", device_id, config);
+
+        self.reopen_device(device_id)
+    }
+
+    /// 整体替换设备流参数覆盖（加载配置时使用）。设备此时可能不在，
+    /// 因此不按能力校验，不支持的参数在打开设备时报告；参数变化的设备重新打开
+    pub fn set_device_configs(
+        &mut self,
+        configs: HashMap<String, DeviceConfig>,
+    ) -> Result<(), AudioError> {
+        let mut changed: Vec<String> = configs
+            .keys()
+            .chain(self.device_configs.keys())
+            .filter(|id| configs.get(*id) != self.device_configs.get(*id))
+            .cloned()
+            .collect();
+        changed.sort();
+        changed.dedup();
+
+        self.device_configs = configs;
+        self.device_configs.retain(|_, config| !config.is_empty());
+        let mut first_error = None;
+        for device_id in changed {
+            if let Err(e) = self.reopen_device(&device_id) {
+                first_error.get_or_insert(e);
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_device_configs(&self) -> HashMap<String, DeviceConfig> {
+        self.device_configs.clone()
+    }
+
+    /// 关闭设备的流和所有经过它的路由节点，由 apply_graph 按新参数重建
+    fn reopen_device(&mut self, device_id: &str) -> Result<(), AudioError> {
+        if !self.input_streams.contains_key(device_id)
+            && !self.output_streams.contains_key(device_id)
+        {
+            return Ok(());
+        }
+
+        self.close_device(device_id);
+        tracing::info!("Reopening device {} with new stream config", device_id);
+
+        self.apply_graph()
+    }
+
+    /// 立即关闭设备的流，并丢弃所有经过它的路由节点（不淡出）
+    fn close_device(&mut self, device_id: &str) {
+        let touches = |route: &Route| {
+            route.input_device_id == device_id || route.output_device_id == device_id
+        };
+        let stale: Vec<Arc<RouteNode>> = self
+            .route_nodes
+            .values()
+            .chain(self.retiring.iter())
+            .filter(|node| touches(&node.route))
+            .cloned()
+            .collect();
+        self.route_nodes.retain(|_, node| !touches(&node.route));
+        self.retiring.retain(|node| !touches(&node.route));
+        for node in stale {
+            if let Some(fanout) = self.input_fanouts.get(&node.route.input_device_id) {
+                fanout.release(&node.buffer);
+            }
+        }
+
+        self.input_streams.remove(device_id);
+        self.input_fanouts.remove(device_id);
+        self.input_configs.remove(device_id);
+        self.peak_levels.remove(device_id);
+        self.output_streams.remove(device_id);
+        self.output_buses.remove(device_id);
+        self.output_configs.remove(device_id);
+    }
+
     pub fn get_peak_levels(&self) -> std::collections::HashMap<String, f32> {
         self.peak_levels
             .iter()
@@ -333,8 +1018,682 @@ impl AudioEngine {
     }
 }
 
+/// 按 renames 修改 map 的键；新键已有值时保留原值
+fn rename_keys<V>(map: &mut HashMap<String, V>, renames: &HashMap<String, String>) {
+    for (from, to) in renames {
+        if let Some(value) = map.remove(from) {
+            map.entry(to.clone()).or_insert(value);
+        }
+    }
+}
+
+fn route_key(route: &Route) -> RouteKey {
+    (
+        route.input_device_id.clone(),
+        route.output_device_id.clone(),
+    )
+}
+
+/// 路由自带的声道映射与两端声道数匹配时使用它，否则退回默认映射
+fn resolve_channel_map(route: &Route, input_channels: u16, output_channels: u16) -> ChannelMap {
+    match &route.channel_map {
+        Some(map)
+            if map.is_valid()
+                && map.input_channels() == input_channels as usize
+                && map.output_channels() == output_channels as usize =>
+        {
+            map.clone()
+        }
+        Some(_) => {
+            tracing::warn!(
+                "Channel map of route {} -> {} does not match {} -> {} channels, using default",
+                route.input_device_id,
+                route.output_device_id,
+                input_channels,
+                output_channels
+            );
+            ChannelMap::default_for(input_channels, output_channels)
+        }
+        None => ChannelMap::default_for(input_channels, output_channels),
+    }
+}
+
 impl Default for AudioEngine {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::backend::{VirtualBackend, VirtualDevice};
+
+    #[test]
+    fn test_route_applies_gain_on_virtual_devices() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        backend
+            .set_source("mic", Box::new(|block: &mut [f32]| block.fill(0.5)))
+            .unwrap();
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        engine
+            .add_route(Route {
+                gain_db: -6.0,
+                ..Route::new("mic", "speakers")
+            })
+            .unwrap();
+        engine.start().unwrap();
+
+        for _ in 0..10 {
+            backend.process_block();
+        }
+
+        let output = backend.take_output("speakers");
+        assert_eq!(output.len(), 10 * 480 * 2);
+        // 前几个周期用于积累目标延迟
+        let expected = 0.5 * 10.0_f32.powf(-6.0 / 20.0);
+        let tail = &output[output.len() - 480 * 2..];
+        assert!(tail.iter().all(|&s| (s - expected).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_mismatched_sample_rates_are_resampled() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 44100, 2));
+        backend
+            .set_source("mic", Box::new(|block: &mut [f32]| block.fill(0.5)))
+            .unwrap();
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        for _ in 0..100 {
+            backend.process_block();
+        }
+
+        let output = backend.take_output("speakers");
+        assert_eq!(output.len(), 100 * 441 * 2);
+        let tail = &output[output.len() - 441 * 2..];
+        assert!(tail.iter().all(|&s| (s - 0.5).abs() < 0.01));
+    }
+
+    #[test]
+    fn test_mono_input_fills_both_stereo_channels() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 1));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        let mut phase = 0;
+        backend
+            .set_source(
+                "mic",
+                Box::new(move |block: &mut [f32]| {
+                    for sample in block.iter_mut() {
+                        *sample = phase as f32 / 10000.0;
+                        phase += 1;
+                    }
+                }),
+            )
+            .unwrap();
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        engine.set_drift_compensation(false);
+        engine.set_ramp_ms(0.0);
+        engine
+            .set_limiter_settings("speakers", bypass_limiter())
+            .unwrap();
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        for _ in 0..3 {
+            backend.process_block();
+        }
+
+        let output = backend.take_output("speakers");
+        assert_eq!(output.len(), 3 * 480 * 2);
+        for (i, frame) in output.chunks_exact(2).enumerate() {
+            assert_eq!(frame[0], i as f32 / 10000.0);
+            assert_eq!(frame[1], frame[0]);
+        }
+    }
+
+    #[test]
+    fn test_drift_compensation_holds_target_latency() {
+        let backend = VirtualBackend::new();
+        // 输入时钟快 1000 ppm：不补偿时一分钟累积约 60 ms 额外延迟
+        backend.add_device(VirtualDevice::input("mic", 48000, 1).with_clock_drift(1000.0));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 1));
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        engine
+            .add_route(Route {
+                resample_quality: ResampleQuality::Linear,
+                ..Route::new("mic", "speakers")
+            })
+            .unwrap();
+        engine.start().unwrap();
+
+        for _ in 0..6000 {
+            backend.process_block();
+        }
+
+        let buffer = engine.input_fanouts["mic"].consumer("speakers").unwrap();
+        // 在输出回调取走一个 10 ms 周期之后测量
+        let latency_ms = buffer.len() as f32 / 48.0 + 10.0;
+        assert_eq!(buffer.dropped(), 0);
+        assert!(
+            (latency_ms - DEFAULT_TARGET_LATENCY_MS).abs() < 5.0,
+            "{latency_ms}"
+        );
+    }
+
+    #[test]
+    fn test_input_fans_out_to_every_routed_output() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        backend.add_device(VirtualDevice::output("headphones", 48000, 2));
+        backend.add_device(VirtualDevice::output("stream", 44100, 2));
+        backend
+            .set_source("mic", Box::new(|block: &mut [f32]| block.fill(0.5)))
+            .unwrap();
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        for output in ["speakers", "headphones", "stream"] {
+            engine.add_route(Route::new("mic", output)).unwrap();
+        }
+        // 重复添加同一路由不会产生第二个消费者
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        for _ in 0..20 {
+            backend.process_block();
+        }
+
+        assert_eq!(engine.input_fanouts["mic"].consumer_count(), 3);
+        for (output, frames) in [("speakers", 480), ("headphones", 480), ("stream", 441)] {
+            let samples = backend.take_output(output);
+            let tail = &samples[samples.len() - frames * 2..];
+            assert!(tail.iter().all(|&s| (s - 0.5).abs() < 0.01), "{output}");
+
+            let buffer = engine.input_fanouts["mic"].consumer(output).unwrap();
+            assert_eq!(buffer.dropped(), 0);
+        }
+    }
+
+    fn constant_engine() -> (VirtualBackend, AudioEngine) {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        backend
+            .set_source("mic", Box::new(|block: &mut [f32]| block.fill(0.5)))
+            .unwrap();
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        engine.set_drift_compensation(false);
+        engine.set_ramp_ms(0.0);
+        // 限制器的预读会延迟输出，这些测试需要逐样本对齐
+        engine
+            .set_limiter_settings("speakers", bypass_limiter())
+            .unwrap();
+        (backend, engine)
+    }
+
+    fn bypass_limiter() -> LimiterSettings {
+        LimiterSettings {
+            enabled: false,
+            ..LimiterSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_route_changes_apply_while_running() {
+        let (backend, mut engine) = constant_engine();
+        engine.start().unwrap();
+        assert_eq!(backend.active_stream_count(), 0);
+
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        assert_eq!(backend.active_stream_count(), 2);
+        backend.process_block();
+        assert!(backend.take_output("speakers").iter().all(|&s| s == 0.5));
+
+        engine.set_gain("mic", -6.0).unwrap();
+        backend.process_block();
+        let expected = 0.5 * 10.0_f32.powf(-6.0 / 20.0);
+        assert!(backend
+            .take_output("speakers")
+            .iter()
+            .all(|&s| (s - expected).abs() < 1e-6));
+
+        engine.set_route_enabled("mic", "speakers", false).unwrap();
+        assert_eq!(backend.active_stream_count(), 0);
+        engine.set_route_enabled("mic", "speakers", true).unwrap();
+        assert_eq!(backend.active_stream_count(), 2);
+
+        engine.remove_route("mic", "speakers").unwrap();
+        assert_eq!(backend.active_stream_count(), 0);
+        assert!(engine.get_peak_levels().is_empty());
+    }
+
+    #[test]
+    fn test_restart_does_not_duplicate_streams() {
+        let (backend, mut engine) = constant_engine();
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+
+        for _ in 0..3 {
+            engine.start().unwrap();
+            engine.stop().unwrap();
+        }
+        engine.start().unwrap();
+        assert_eq!(backend.active_stream_count(), 2);
+
+        backend.process_block();
+        assert!(backend.take_output("speakers").iter().all(|&s| s == 0.5));
+    }
+
+    fn max_step(samples: &[f32], channels: usize) -> f32 {
+        samples
+            .chunks_exact(channels)
+            .zip(samples.chunks_exact(channels).skip(1))
+            .flat_map(|(a, b)| a.iter().zip(b).map(|(x, y)| (x - y).abs()))
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_gain_changes_and_new_routes_are_ramped() {
+        let (backend, mut engine) = constant_engine();
+        engine.set_ramp_ms(DEFAULT_RAMP_MS);
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        // 10 ms 斜坡 = 一个 480 帧的周期
+        backend.process_block();
+        let fade_in = backend.take_output("speakers");
+        assert!(fade_in[0] < 0.01);
+        assert!((fade_in[fade_in.len() - 1] - 0.5).abs() < 1e-6);
+        assert!(max_step(&fade_in, 2) < 0.01);
+
+        engine.set_gain("mic", -12.0).unwrap();
+        backend.process_block();
+        backend.process_block();
+        let ramp = backend.take_output("speakers");
+        let expected = 0.5 * 10.0_f32.powf(-12.0 / 20.0);
+        assert!(ramp[0] > expected + 0.1);
+        assert!((ramp[ramp.len() - 1] - expected).abs() < 1e-6);
+        assert!(max_step(&ramp, 2) < 0.01);
+    }
+
+    #[test]
+    fn test_removed_route_fades_out_before_streams_close() {
+        let (backend, mut engine) = constant_engine();
+        engine.set_ramp_ms(DEFAULT_RAMP_MS);
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+        for _ in 0..3 {
+            backend.process_block();
+        }
+        backend.take_output("speakers");
+
+        // 控制命令不等待淡出，节点在下一次检查时移除；10 ms 淡出 = 一个周期
+        engine.remove_route("mic", "speakers").unwrap();
+        assert_eq!(backend.active_stream_count(), 2);
+        for _ in 0..2 {
+            backend.process_block();
+        }
+        engine.recover_streams();
+
+        assert_eq!(backend.active_stream_count(), 0);
+        let output = backend.take_output("speakers");
+        assert!(output.iter().any(|&s| s > 0.0 && s < 0.5));
+        assert_eq!(output[output.len() - 1], 0.0);
+        assert!(max_step(&output, 2) < 0.01);
+    }
+
+    #[test]
+    fn test_mix_mode_controls_level_of_summed_routes() {
+        let (backend, mut engine) = constant_engine();
+        // 第二个输入静音：平均模式会把 mic 压低 6 dB
+        backend.add_device(VirtualDevice::input("line", 48000, 2));
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.add_route(Route::new("line", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        let cases = [
+            (MixMode::Average, 0.25),
+            (MixMode::EqualPower, 0.5 * std::f32::consts::FRAC_1_SQRT_2),
+            (
+                MixMode::Headroom { headroom_db: 6.0 },
+                0.5 * 10.0_f32.powf(-6.0 / 20.0),
+            ),
+            (MixMode::Sum, 0.5),
+        ];
+        for (mode, expected) in cases {
+            engine.set_mix_mode("speakers", mode).unwrap();
+            backend.process_block();
+            let output = backend.take_output("speakers");
+            let tail = &output[output.len() - 100..];
+            assert!(
+                tail.iter().all(|&s| (s - expected).abs() < 1e-5),
+                "{mode:?}: {}",
+                tail[0]
+            );
+        }
+        assert_eq!(engine.get_mix_modes()["speakers"], MixMode::Sum);
+        // 负的衰减量被拒绝，原来的设置不变
+        assert!(engine
+            .set_mix_mode("speakers", MixMode::Headroom { headroom_db: -6.0 })
+            .is_err());
+        assert_eq!(engine.get_mix_modes()["speakers"], MixMode::Sum);
+    }
+
+    #[test]
+    fn test_limiter_keeps_summed_routes_below_ceiling() {
+        let (backend, mut engine) = constant_engine();
+        backend.add_device(VirtualDevice::input("line", 48000, 2));
+        backend
+            .set_source("line", Box::new(|block: &mut [f32]| block.fill(0.5)))
+            .unwrap();
+        engine.set_mix_mode("speakers", MixMode::Sum).unwrap();
+        engine
+            .set_limiter_settings("speakers", LimiterSettings::default())
+            .unwrap();
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.add_route(Route::new("line", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        for _ in 0..10 {
+            backend.process_block();
+        }
+
+        let ceiling = 10.0_f32.powf(LimiterSettings::default().ceiling_db / 20.0);
+        let output = backend.take_output("speakers");
+        assert!(output.iter().all(|&s| s <= ceiling + 1e-4));
+        assert!(output[output.len() - 1] > 0.7);
+        assert!(engine.get_gain_reduction()["speakers"] > 1.0);
+    }
+
+    #[test]
+    fn test_integer_devices_are_converted_to_and_from_f32() {
+        use crate::audio::sample_format::SampleFormat;
+
+        let backend = VirtualBackend::new();
+        backend.add_device(
+            VirtualDevice::input("mic", 48000, 2).with_sample_format(SampleFormat::I16),
+        );
+        backend.add_device(
+            VirtualDevice::output("speakers", 48000, 2).with_sample_format(SampleFormat::U8),
+        );
+        backend
+            .set_source("mic", Box::new(|block: &mut [f32]| block.fill(0.3)))
+            .unwrap();
+
+        let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+        engine.set_drift_compensation(false);
+        engine.set_ramp_ms(0.0);
+        engine
+            .set_limiter_settings("speakers", bypass_limiter())
+            .unwrap();
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+        backend.process_block();
+
+        // u8 输出加了抖动：每个样本在 ±1 LSB 以内，平均值接近原信号
+        let output = backend.take_output("speakers");
+        assert!(output.iter().all(|&s| (s - 0.3).abs() <= 1.5 / 128.0));
+        let mean = output.iter().sum::<f32>() / output.len() as f32;
+        assert!((mean - 0.3).abs() < 0.1 / 128.0, "{mean}");
+    }
+
+    #[test]
+    fn test_device_config_overrides_are_validated_and_applied() {
+        use crate::audio::backend::SupportedConfigRange;
+        use crate::audio::sample_format::SampleFormat;
+
+        let (backend, mut engine) = constant_engine();
+        backend.add_device(
+            VirtualDevice::output("speakers", 48000, 2).with_supported_configs(vec![
+                SupportedConfigRange {
+                    channels: 2,
+                    min_sample_rate: 44100,
+                    max_sample_rate: 48000,
+                    min_buffer_frames: Some(64),
+                    max_buffer_frames: Some(4096),
+                    sample_format: SampleFormat::F32,
+                },
+            ]),
+        );
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        let unsupported = DeviceConfig {
+            sample_rate: Some(96000),
+            ..DeviceConfig::default()
+        };
+        assert!(engine.set_device_config("speakers", unsupported).is_err());
+        let too_small = DeviceConfig {
+            buffer_frames: Some(16),
+            ..DeviceConfig::default()
+        };
+        assert!(engine.set_device_config("speakers", too_small).is_err());
+        assert!(engine.get_device_configs().is_empty());
+
+        let config = DeviceConfig {
+            sample_rate: Some(44100),
+            buffer_frames: Some(256),
+            ..DeviceConfig::default()
+        };
+        engine.set_device_config("speakers", config).unwrap();
+        assert_eq!(backend.active_stream_count(), 2);
+
+        backend.take_output("speakers");
+        backend.process_block();
+        assert_eq!(backend.take_output("speakers").len(), 441 * 2);
+        assert_eq!(engine.get_device_configs()["speakers"], config);
+    }
+
+    #[test]
+    fn test_unplugged_device_degrades_routes_and_recovers() {
+        let (backend, mut engine) = constant_engine();
+        backend.add_device(VirtualDevice::output("headset", 48000, 2));
+        engine
+            .set_limiter_settings("headset", bypass_limiter())
+            .unwrap();
+        engine.set_retry_delay(Duration::ZERO);
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.add_route(Route::new("mic", "headset")).unwrap();
+        engine.start().unwrap();
+        assert!(!engine.recover_streams());
+
+        backend.remove_device("headset");
+        assert!(engine.recover_streams());
+        let health = engine.stream_health();
+        assert_eq!(health.failed_devices.len(), 1);
+        assert_eq!(health.failed_devices[0].device_id, "headset");
+        assert_eq!(health.degraded_routes.len(), 1);
+        assert_eq!(health.degraded_routes[0].output_device_id, "headset");
+        assert_eq!(backend.active_stream_count(), 2);
+
+        // 其他路由不受影响
+        backend.take_output("speakers");
+        backend.process_block();
+        assert!(backend.take_output("speakers").iter().all(|&s| s == 0.5));
+
+        // 设备仍然不在时重试失败，状态不变
+        let attempts = health.failed_devices[0].attempts;
+        assert!(!engine.recover_streams());
+        assert_eq!(
+            engine.stream_health().failed_devices[0].attempts,
+            attempts + 1
+        );
+
+        backend.add_device(VirtualDevice::output("headset", 48000, 2));
+        assert!(engine.recover_streams());
+        assert!(engine.stream_health().failed_devices.is_empty());
+        assert_eq!(backend.active_stream_count(), 3);
+        backend.process_block();
+        let output = backend.take_output("headset");
+        assert!(!output.is_empty() && output.iter().all(|&s| s == 0.5));
+    }
+
+    #[test]
+    fn test_route_of_unplugged_duplicate_is_not_remapped() {
+        let (backend, mut engine) = constant_engine();
+        backend.add_device(VirtualDevice::input("input:USB Audio CODEC", 48000, 2));
+        backend.add_device(VirtualDevice::input("input:USB Audio CODEC#2", 48000, 2));
+        engine.set_retry_delay(Duration::ZERO);
+        let route = Route::new("input:USB Audio CODEC#2", "speakers");
+        engine.add_route(route.clone()).unwrap();
+        engine.start().unwrap();
+
+        backend.remove_device("input:USB Audio CODEC#2");
+        engine.recover_streams();
+        // 重新应用保存的路由（启动或切换档案时）也不会转移到另一个同名设备；
+        // 设备不在时返回错误，但路由保留
+        let _ = engine.set_routes(vec![route.clone()]);
+        let _ = engine.add_route(route);
+        let routes = engine.get_routes();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].input_device_id, "input:USB Audio CODEC#2");
+        assert_eq!(
+            engine.stream_health().failed_devices[0].device_id,
+            "input:USB Audio CODEC#2"
+        );
+
+        backend.add_device(VirtualDevice::input("input:USB Audio CODEC#2", 48000, 2));
+        assert!(engine.recover_streams());
+        assert!(engine.stream_health().failed_devices.is_empty());
+    }
+
+    #[test]
+    fn test_legacy_ids_are_resolved_when_the_device_appears() {
+        let (backend, mut engine) = constant_engine();
+        engine.set_retry_delay(Duration::ZERO);
+        // 升级前保存的设置，设备在启动时还没有插入
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine
+            .add_route(Route::new("mic", "Studio Monitors_output"))
+            .unwrap();
+        engine
+            .set_mix_mode("Studio Monitors_output", MixMode::Sum)
+            .unwrap();
+        engine
+            .set_limiter_settings("Studio Monitors_output", bypass_limiter())
+            .unwrap();
+        engine.start().unwrap();
+        assert_eq!(
+            engine.stream_health().failed_devices[0].device_id,
+            "Studio Monitors_output"
+        );
+
+        backend.add_device(VirtualDevice {
+            name: "Studio Monitors".into(),
+            ..VirtualDevice::output("output:{monitors}", 48000, 2)
+        });
+        assert!(engine.recover_streams());
+        assert!(engine.stream_health().failed_devices.is_empty());
+        assert_eq!(engine.get_routes()[1].output_device_id, "output:{monitors}");
+        assert_eq!(engine.get_mix_modes()["output:{monitors}"], MixMode::Sum);
+        assert!(!engine.limiter_settings("output:{monitors}").enabled);
+        assert!(!engine
+            .get_mix_modes()
+            .contains_key("Studio Monitors_output"));
+
+        backend.process_block();
+        assert!(backend
+            .take_output("output:{monitors}")
+            .iter()
+            .all(|&s| s == 0.5));
+    }
+
+    #[test]
+    fn test_start_runs_every_route_it_can_and_reports_failures() {
+        let (backend, mut engine) = constant_engine();
+        engine.set_retry_delay(Duration::ZERO);
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.add_route(Route::new("mic", "missing")).unwrap();
+        assert_eq!(engine.state(), EngineState::Stopped);
+
+        let status = engine.start().unwrap();
+        assert_eq!(status.state, EngineState::Degraded);
+        assert!(engine.is_running());
+        assert_eq!(status.health.failed_devices.len(), 1);
+        assert_eq!(status.health.failed_devices[0].device_id, "missing");
+        assert_eq!(status.health.degraded_routes.len(), 1);
+
+        backend.process_block();
+        assert!(backend.take_output("speakers").iter().all(|&s| s == 0.5));
+
+        backend.add_device(VirtualDevice::output("missing", 48000, 2));
+        assert!(engine.recover_streams());
+        assert_eq!(engine.status().state, EngineState::Running);
+
+        engine.stop().unwrap();
+        assert_eq!(engine.state(), EngineState::Stopped);
+        assert!(!engine.is_running());
+    }
+
+    #[test]
+    fn test_start_fails_when_no_stream_can_be_opened() {
+        let (backend, mut engine) = constant_engine();
+        engine
+            .add_route(Route::new("missing-in", "missing-out"))
+            .unwrap();
+
+        assert!(engine.start().is_err());
+        assert_eq!(engine.state(), EngineState::Stopped);
+        assert!(!engine.is_running());
+        assert_eq!(backend.active_stream_count(), 0);
+    }
+
+    #[test]
+    fn test_stop_start_cycles_release_streams_and_buffers() {
+        let (backend, mut engine) = constant_engine();
+        backend.add_device(VirtualDevice::output("headset", 48000, 2));
+        engine
+            .set_limiter_settings("headset", bypass_limiter())
+            .unwrap();
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.add_route(Route::new("mic", "headset")).unwrap();
+
+        for cycle in 0..50 {
+            let status = if cycle % 2 == 0 {
+                engine.start().unwrap()
+            } else {
+                engine.restart().unwrap()
+            };
+            assert_eq!(status.state, EngineState::Running);
+            assert_eq!(backend.active_stream_count(), 3);
+            // 每个输入的缓冲区只被当前的两条路由持有
+            assert_eq!(engine.input_fanouts["mic"].consumer_count(), 2);
+            let nodes: Vec<_> = engine.route_nodes.values().map(Arc::downgrade).collect();
+            let buffers: Vec<_> = engine
+                .route_nodes
+                .values()
+                .map(|node| Arc::downgrade(&node.buffer))
+                .collect();
+
+            // 停止后缓冲区里还有未播放的数据
+            backend.process_block();
+            engine.stop().unwrap();
+            assert_eq!(backend.active_stream_count(), 0);
+            assert!(engine.input_fanouts.is_empty() && engine.output_buses.is_empty());
+            assert!(engine.peak_levels.is_empty());
+            assert!(nodes.iter().all(|n| n.upgrade().is_none()));
+            assert!(buffers.iter().all(|b| b.upgrade().is_none()));
+        }
+
+        // 重新启动后不会播放停止前残留的音频
+        backend
+            .set_source("mic", Box::new(|block: &mut [f32]| block.fill(0.25)))
+            .unwrap();
+        backend.take_output("speakers");
+        engine.start().unwrap();
+        backend.process_block();
+        let output = backend.take_output("speakers");
+        assert!(!output.is_empty() && output.iter().all(|&s| s == 0.25));
+    }
+}
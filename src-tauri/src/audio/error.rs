@@ -37,6 +37,12 @@ pub enum AudioError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("WAV error: {0}")]
+    Wav(#[from] hound::Error),
+
     #[error("Configuration error: {0}")]
     Config(String),
+
+    #[error("Configuration file changed on disk and has not been applied yet")]
+    ConfigPending,
 }
@@ -0,0 +1,87 @@
+use super::ring_buffer::RingBuffer;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+
+/// 把一个输入设备的音频广播给它的所有路由。
+///
+/// 每条路由（按输出设备区分）拥有独立的环形缓冲区，各输出按自己的节奏
+/// 消费，互不抢占数据；某个输出停滞时也只会丢弃它自己的数据。
+/// 订阅者列表通过 ArcSwap 替换，输入回调读取时无锁。
+pub struct InputFanout {
+    consumers: ArcSwap<Vec<(String, Arc<RingBuffer>)>>,
+    capacity: usize,
+}
+
+impl InputFanout {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            consumers: ArcSwap::from_pointee(Vec::new()),
+            capacity,
+        }
+    }
+
+    /// 为输出设备注册一个消费者缓冲区；已存在时返回原缓冲区
+    pub fn subscribe(&self, output_device_id: &str) -> Arc<RingBuffer> {
+        if let Some(buffer) = self.consumer(output_device_id) {
+            return buffer;
+        }
+
+        let buffer = Arc::new(RingBuffer::new(self.capacity));
+        self.consumers.rcu(|consumers| {
+            let mut consumers = (**consumers).clone();
+            consumers.retain(|(id, _)| id != output_device_id);
+            consumers.push((output_device_id.to_string(), Arc::clone(&buffer)));
+            consumers
+        });
+        buffer
+    }
+
+    /// 为输出设备注册一个新的消费者缓冲区。原缓冲区继续接收数据，
+    /// 直到被 release，用于新旧路由节点交叉淡化
+    pub fn replace(&self, output_device_id: &str) -> Arc<RingBuffer> {
+        let buffer = Arc::new(RingBuffer::new(self.capacity));
+        self.consumers.rcu(|consumers| {
+            let mut consumers = (**consumers).clone();
+            consumers.push((output_device_id.to_string(), Arc::clone(&buffer)));
+            consumers
+        });
+        buffer
+    }
+
+    /// 移除指定的消费者缓冲区
+    pub fn release(&self, buffer: &Arc<RingBuffer>) {
+        self.consumers.rcu(|consumers| {
+            let mut consumers = (**consumers).clone();
+            consumers.retain(|(_, b)| !Arc::ptr_eq(b, buffer));
+            consumers
+        });
+    }
+
+    pub fn unsubscribe(&self, output_device_id: &str) {
+        self.consumers.rcu(|consumers| {
+            let mut consumers = (**consumers).clone();
+            consumers.retain(|(id, _)| id != output_device_id);
+            consumers
+        });
+    }
+
+    /// 输出设备当前（最新注册）的消费者缓冲区
+    pub fn consumer(&self, output_device_id: &str) -> Option<Arc<RingBuffer>> {
+        self.consumers
+            .load()
+            .iter()
+            .rfind(|(id, _)| id == output_device_id)
+            .map(|(_, buffer)| Arc::clone(buffer))
+    }
+
+    pub fn consumer_count(&self) -> usize {
+        self.consumers.load().len()
+    }
+
+    /// 把一块输入数据写入每个消费者（在输入回调中调用）
+    pub fn broadcast(&self, data: &[f32]) {
+        for (_, buffer) in self.consumers.load().iter() {
+            buffer.write(data);
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use super::{
+    backend::StreamConfig,
+    channels::ChannelMap,
+    drift::{DriftController, MAX_ADJUST},
+    engine::Route,
+    limiter::{Limiter, LimiterSettings},
+    mixer::MixMode,
+    resampler::Resampler,
+    ring_buffer::RingBuffer,
+};
+use arc_swap::ArcSwap;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// 输出回调每次从路由缓冲区读取的帧数
+pub(crate) const READ_CHUNK_FRAMES: usize = 64;
+/// 输出回调一次混合的最大帧数，暂存区按此预先分配；更大的回调分段混合，
+/// 不在音频线程中扩容
+pub(crate) const MAX_CALLBACK_FRAMES: usize = 4096;
+
+/// 输出回调中每条路由的 DSP 状态
+pub(crate) struct RouteSource {
+    pub buffer: Arc<RingBuffer>,
+    pub input_channels: usize,
+    pub channel_map: Option<ChannelMap>,
+    pub resampler: Option<Resampler>,
+    /// 重采样器是否在工作；采样率相同且漂移在死区内时直通
+    pub resampling: bool,
+    pub nominal_ratio: f64,
+    pub drift: Option<DriftController>,
+    /// 启动或欠载后等待缓冲区填充到目标延迟
+    pub priming: bool,
+    pub pending: VecDeque<f32>,
+    pub read_buffer: Vec<f32>,
+    pub map_buffer: Vec<f32>,
+    pub resample_buffer: Vec<f32>,
+    /// 读取一块输入最多产生的输出样本数
+    chunk_samples: usize,
+}
+
+impl RouteSource {
+    /// 按输出回调的最大帧数和最小转换比例预先分配所有暂存区，
+    /// 之后 pull 只在已有容量内工作
+    pub fn new(
+        buffer: Arc<RingBuffer>,
+        input_channels: usize,
+        channel_map: Option<ChannelMap>,
+        mut resampler: Option<Resampler>,
+        nominal_ratio: f64,
+        drift: Option<DriftController>,
+        max_frames: usize,
+    ) -> Self {
+        let input_channels = input_channels.max(1);
+        let output_channels = match &channel_map {
+            Some(map) => map.output_channels().max(1),
+            None => input_channels,
+        };
+        // 漂移补偿可以把比例再降低 MAX_ADJUST，此时每块输入产生的输出最多；
+        // 切换回直通时还要放下重采样器中剩余的输入
+        let (chunk_frames, drain_frames) = match &mut resampler {
+            Some(resampler) => {
+                resampler.reserve(READ_CHUNK_FRAMES);
+                let min_ratio = nominal_ratio * (1.0 - MAX_ADJUST);
+                (
+                    (READ_CHUNK_FRAMES as f64 / min_ratio).ceil() as usize + 1,
+                    resampler.buffer_frames(READ_CHUNK_FRAMES),
+                )
+            }
+            None => (READ_CHUNK_FRAMES, 0),
+        };
+        let chunk_samples = chunk_frames * output_channels;
+        let drain_samples = drain_frames * output_channels;
+
+        Self {
+            buffer,
+            input_channels,
+            channel_map,
+            resampling: resampler.is_some() && nominal_ratio != 1.0,
+            resampler,
+            nominal_ratio,
+            priming: drift.is_some(),
+            drift,
+            pending: VecDeque::with_capacity(
+                max_frames * output_channels + chunk_samples + drain_samples,
+            ),
+            read_buffer: vec![0.0; READ_CHUNK_FRAMES * input_channels],
+            map_buffer: Vec::with_capacity(READ_CHUNK_FRAMES * output_channels),
+            resample_buffer: Vec::with_capacity(chunk_samples.max(drain_samples)),
+            chunk_samples,
+        }
+    }
+
+    /// 输出声道数（经过声道映射之后）
+    fn output_channels(&self) -> usize {
+        match &self.channel_map {
+            Some(map) => map.output_channels().max(1),
+            None => self.input_channels,
+        }
+    }
+
+    /// 准备最多 wanted 个输出样本到 pending 中，返回实际可用的样本数
+    fn pull(&mut self, wanted: usize) -> usize {
+        let mut resample = self.nominal_ratio != 1.0;
+        if let Some(drift) = &mut self.drift {
+            let fill_frames = self.buffer.len() / self.input_channels;
+            if self.priming {
+                if (fill_frames as f64) < drift.target_frames() {
+                    return 0;
+                }
+                self.priming = false;
+                drift.reset();
+            }
+
+            let adjust = drift.update(fill_frames);
+            resample |= drift.is_correcting();
+            if let Some(resampler) = &mut self.resampler {
+                resampler.set_ratio(self.nominal_ratio * adjust);
+            }
+        }
+
+        // 在直通和重采样之间切换时从同一输入帧继续，信号保持连续
+        if let Some(resampler) = &mut self.resampler {
+            if resample && !self.resampling {
+                resampler.restart();
+                self.resampling = true;
+            } else if !resample
+                && self.resampling
+                && self.pending.capacity() - self.pending.len() >= self.resample_buffer.capacity()
+            {
+                self.resample_buffer.clear();
+                resampler.drain_input(&mut self.resample_buffer);
+                self.pending.extend(self.resample_buffer.iter());
+                self.resampling = false;
+            }
+        }
+
+        while self.pending.len() < wanted {
+            // 剩余容量放不下一块的输出时停止读取，不在音频线程中扩容
+            if self.pending.capacity() - self.pending.len() < self.chunk_samples {
+                break;
+            }
+            let read = self.buffer.read(&mut self.read_buffer);
+            if read == 0 {
+                // 欠载：重新积累到目标延迟，避免之后持续处于临界状态
+                self.priming = self.drift.is_some();
+                break;
+            }
+
+            let chunk = &self.read_buffer[..read];
+            let mapped: &[f32] = match &self.channel_map {
+                Some(map) => {
+                    self.map_buffer.clear();
+                    map.apply(chunk, &mut self.map_buffer);
+                    &self.map_buffer
+                }
+                None => chunk,
+            };
+
+            match &mut self.resampler {
+                Some(resampler) if self.resampling => {
+                    self.resample_buffer.clear();
+                    resampler.process(mapped, &mut self.resample_buffer);
+                    self.pending.extend(self.resample_buffer.iter());
+                }
+                _ => self.pending.extend(mapped.iter()),
+            }
+        }
+
+        self.pending.len().min(wanted)
+    }
+}
+
+/// 线性斜坡：在给定帧数内从当前值匀速移动到目标值
+pub(crate) struct Ramp {
+    current: f32,
+    target: f32,
+    step: f32,
+}
+
+impl Ramp {
+    pub fn new(value: f32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            step: 0.0,
+        }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn set_target(&mut self, target: f32, frames: usize) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        self.step = if frames == 0 {
+            f32::INFINITY
+        } else {
+            (target - self.current).abs() / frames as f32
+        };
+    }
+
+    /// 前进一帧，返回该帧使用的值
+    pub fn next(&mut self) -> f32 {
+        if self.current < self.target {
+            self.current = (self.current + self.step).min(self.target);
+        } else if self.current > self.target {
+            self.current = (self.current - self.step).max(self.target);
+        }
+        self.current
+    }
+}
+
+/// 输出回调独占的路由状态：DSP 链和增益、淡入淡出斜坡
+struct NodeState {
+    source: RouteSource,
+    gain: Ramp,
+    fade: Ramp,
+}
+
+/// 运行中的一条路由。
+///
+/// 目标增益和开关以原子变量保存，控制线程可以直接修改，输出回调在斜坡时间内
+/// 平滑过渡到目标值；DSP 状态只由所属的输出回调访问，节点在图更新之间复用，
+/// 因此重采样器和缓冲区状态不会因为其他路由的变化而重置。
+pub(crate) struct RouteNode {
+    pub route: Route,
+    /// 本路由在输入广播器中的消费者缓冲区
+    pub buffer: Arc<RingBuffer>,
+    gain: AtomicU32,
+    /// 为 false 时淡出，淡出完成后 finished 置位，等待控制线程从图中移除
+    active: AtomicBool,
+    finished: AtomicBool,
+    state: parking_lot::Mutex<NodeState>,
+}
+
+impl RouteNode {
+    /// 新节点从静音淡入
+    pub fn new(route: Route, source: RouteSource) -> Self {
+        let gain = db_to_linear(route.gain_db);
+        Self {
+            route,
+            buffer: Arc::clone(&source.buffer),
+            gain: AtomicU32::new(gain.to_bits()),
+            active: AtomicBool::new(true),
+            finished: AtomicBool::new(false),
+            state: parking_lot::Mutex::new(NodeState {
+                source,
+                gain: Ramp::new(gain),
+                fade: Ramp::new(0.0),
+            }),
+        }
+    }
+
+    pub fn set_gain_db(&self, gain_db: f32) {
+        self.gain
+            .store(db_to_linear(gain_db).to_bits(), Ordering::Relaxed);
+    }
+
+    fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    /// 开始淡出，之后由输出回调报告完成
+    pub fn fade_out(&self) {
+        self.active.store(false, Ordering::Release);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// 将本路由混入 mix_buffer，并把每帧的淡入淡出系数累加到 weights，
+    /// 返回混入的样本数
+    fn mix_into(
+        &self,
+        mix_buffer: &mut [f32],
+        weights: &mut [f32],
+        channels: usize,
+        ramp_frames: usize,
+    ) -> usize {
+        let Some(mut state) = self.state.try_lock() else {
+            return 0;
+        };
+        let state = &mut *state;
+        let active = self.active.load(Ordering::Acquire);
+
+        state.gain.set_target(self.gain(), ramp_frames);
+        state
+            .fade
+            .set_target(if active { 1.0 } else { 0.0 }, ramp_frames);
+
+        // 声道数不一致时（声道映射无效）按输出声道数截断或补零
+        let source_channels = state.source.output_channels();
+        let frames = mix_buffer.len() / channels;
+        let available = state.source.pull(frames * source_channels) / source_channels;
+
+        for (frame, weight) in weights.iter_mut().enumerate().take(frames) {
+            if frame < available {
+                let gain = state.gain.next() * state.fade.next();
+                let out = &mut mix_buffer[frame * channels..(frame + 1) * channels];
+                for (c, sample) in state.source.pending.drain(..source_channels).enumerate() {
+                    if let Some(slot) = out.get_mut(c) {
+                        *slot += sample * gain;
+                    }
+                }
+            } else if !active {
+                // 淡出期间数据耗尽时继续推进，保证节点能够结束
+                state.fade.next();
+            }
+            *weight += state.fade.current();
+        }
+
+        if !active && state.fade.current() == 0.0 {
+            self.finished.store(true, Ordering::Release);
+        }
+        available * channels
+    }
+
+    /// 除增益和开关外，其余参数变化都需要重建节点
+    pub fn is_compatible(&self, route: &Route) -> bool {
+        self.route.resample_quality == route.resample_quality
+            && self.route.channel_map == route.channel_map
+    }
+}
+
+/// 一个输出设备当前的路由列表，整体原子替换
+pub(crate) struct OutputBus {
+    routes: ArcSwap<Vec<Arc<RouteNode>>>,
+    mix_mode: ArcSwap<MixMode>,
+    limiter: ArcSwap<LimiterSettings>,
+    /// 最近一次回调中限制器的最大增益衰减（dB，f32 位模式）
+    gain_reduction: AtomicU32,
+    channels: usize,
+    sample_rate: u32,
+    /// 增益变化和淡入淡出的斜坡长度（帧）
+    ramp_frames: AtomicUsize,
+}
+
+/// 输出回调持有的暂存区和限制器状态
+pub(crate) struct MixScratch {
+    mix: Vec<f32>,
+    weights: Vec<f32>,
+    limiter: Limiter,
+}
+
+impl OutputBus {
+    pub fn new(
+        config: &StreamConfig,
+        mix_mode: MixMode,
+        limiter: LimiterSettings,
+        ramp_frames: usize,
+    ) -> Self {
+        Self {
+            routes: ArcSwap::from_pointee(Vec::new()),
+            mix_mode: ArcSwap::from_pointee(mix_mode),
+            limiter: ArcSwap::from_pointee(limiter),
+            gain_reduction: AtomicU32::new(0.0f32.to_bits()),
+            channels: config.channels.max(1) as usize,
+            sample_rate: config.sample_rate,
+            ramp_frames: AtomicUsize::new(ramp_frames),
+        }
+    }
+
+    /// 为输出回调创建暂存区
+    pub fn scratch(&self) -> MixScratch {
+        MixScratch {
+            mix: Vec::with_capacity(MAX_CALLBACK_FRAMES * self.channels),
+            weights: Vec::with_capacity(MAX_CALLBACK_FRAMES),
+            limiter: Limiter::new(
+                self.channels as u16,
+                self.sample_rate,
+                **self.limiter.load(),
+            ),
+        }
+    }
+
+    pub fn set_mix_mode(&self, mode: MixMode) {
+        self.mix_mode.store(Arc::new(mode));
+    }
+
+    pub fn set_limiter(&self, settings: LimiterSettings) {
+        self.limiter.store(Arc::new(settings));
+    }
+
+    pub fn gain_reduction_db(&self) -> f32 {
+        f32::from_bits(self.gain_reduction.load(Ordering::Relaxed))
+    }
+
+    pub fn set_ramp_frames(&self, frames: usize) {
+        self.ramp_frames.store(frames, Ordering::Relaxed);
+    }
+
+    pub fn set_routes(&self, routes: Vec<Arc<RouteNode>>) {
+        self.routes.store(Arc::new(routes));
+    }
+
+    /// 输出回调：混合当前图中的所有路由。只使用 try_lock 和无锁读取，
+    /// 不会阻塞音频线程。混合增益按每帧的淡入淡出系数之和计算，
+    /// 路由增减时电平平滑过渡。超过 `MAX_CALLBACK_FRAMES` 的回调分段处理，
+    /// 暂存区不会扩容
+    pub fn render(&self, output: &mut [f32], scratch: &mut MixScratch) {
+        let routes = self.routes.load();
+        let mix_mode = **self.mix_mode.load();
+        let ramp_frames = self.ramp_frames.load(Ordering::Relaxed);
+        scratch.limiter.set_settings(**self.limiter.load());
+
+        for output in output.chunks_mut(MAX_CALLBACK_FRAMES * self.channels) {
+            let frames = output.len() / self.channels;
+            scratch.mix.clear();
+            scratch.mix.resize(output.len(), 0.0);
+            scratch.weights.clear();
+            scratch.weights.resize(frames, 0.0);
+            let mut total_samples = 0;
+
+            for node in routes.iter() {
+                total_samples += node.mix_into(
+                    &mut scratch.mix,
+                    &mut scratch.weights,
+                    self.channels,
+                    ramp_frames,
+                );
+            }
+
+            if total_samples > 0 {
+                for (frame, &weight) in scratch.mix.chunks_mut(self.channels).zip(&scratch.weights)
+                {
+                    let gain = mix_mode.gain(weight);
+                    for sample in frame {
+                        *sample *= gain;
+                    }
+                }
+            }
+            scratch.limiter.process(&mut scratch.mix);
+
+            // 限制器关闭时的最后保护
+            for sample in scratch.mix.iter_mut() {
+                *sample = sample.clamp(-1.0, 1.0);
+            }
+
+            output.copy_from_slice(&scratch.mix);
+        }
+
+        self.gain_reduction.store(
+            scratch.limiter.take_gain_reduction_db().to_bits(),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::resampler::ResampleQuality;
+
+    #[test]
+    fn test_pull_never_grows_its_buffers() {
+        let buffer = Arc::new(RingBuffer::new(48000 * 2));
+        let resampler = Resampler::new(ResampleQuality::Sinc, 44100, 48000, 2);
+        let mut source = RouteSource::new(
+            Arc::clone(&buffer),
+            2,
+            None,
+            Some(resampler),
+            44100.0 / 48000.0,
+            Some(DriftController::new(441.0)),
+            256,
+        );
+        let capacities =
+            |source: &RouteSource| (source.pending.capacity(), source.resample_buffer.capacity());
+        let before = capacities(&source);
+
+        // 回调比预期大得多，缓冲区一直很满：只提供已分配容量内的数据
+        buffer.write(&[0.25; 44100 * 2]);
+        for _ in 0..20 {
+            let available = source.pull(4096 * 2);
+            assert!(available > 0 && available <= before.0);
+            source.pending.drain(..available);
+        }
+        assert_eq!(capacities(&source), before);
+        assert!(!source.priming);
+    }
+
+    #[test]
+    fn test_large_callbacks_are_mixed_in_slices() {
+        let config = StreamConfig {
+            sample_rate: 48000,
+            channels: 2,
+            buffer_frames: Some(10_000),
+            sample_format: Default::default(),
+        };
+        let limiter = LimiterSettings {
+            enabled: false,
+            ..LimiterSettings::default()
+        };
+        let bus = OutputBus::new(&config, MixMode::Sum, limiter, 0);
+        let buffer = Arc::new(RingBuffer::new(48000 * 2));
+        buffer.write(&[0.5; 20_000 * 2]);
+        let source = RouteSource::new(buffer, 2, None, None, 1.0, None, MAX_CALLBACK_FRAMES);
+        bus.set_routes(vec![Arc::new(RouteNode::new(
+            Route::new("mic", "speakers"),
+            source,
+        ))]);
+
+        let mut scratch = bus.scratch();
+        let capacity = (scratch.mix.capacity(), scratch.weights.capacity());
+        let mut output = vec![0.0; 10_000 * 2];
+        bus.render(&mut output, &mut scratch);
+        assert!(output.iter().all(|&s| s == 0.5));
+        assert_eq!(
+            (scratch.mix.capacity(), scratch.weights.capacity()),
+            capacity
+        );
+    }
+
+    #[test]
+    fn test_same_rate_route_bypasses_resampler_inside_deadband() {
+        let buffer = Arc::new(RingBuffer::new(48000));
+        let resampler = Resampler::new(ResampleQuality::Sinc, 48000, 48000, 1);
+        let mut source = RouteSource::new(
+            Arc::clone(&buffer),
+            1,
+            None,
+            Some(resampler),
+            1.0,
+            Some(DriftController::new(480.0)),
+            256,
+        );
+        assert!(!source.resampling);
+
+        let signal = |i: usize| (i as f32 * 0.013).sin();
+        let mut written = 0;
+        let mut write = |frames: usize| {
+            let block: Vec<f32> = (written..written + frames).map(signal).collect();
+            buffer.write(&block);
+            written += frames;
+        };
+        let pull = |source: &mut RouteSource, output: &mut Vec<f32>| {
+            let available = source.pull(256);
+            output.extend(source.pending.drain(..available));
+        };
+        let mut output = Vec::new();
+
+        // 同一时钟：填充度稳定在目标附近，输出与输入逐样本相同
+        write(480);
+        for _ in 0..100 {
+            pull(&mut source, &mut output);
+            write(256);
+        }
+        assert!(!source.resampling);
+        assert!(output.iter().enumerate().all(|(i, &s)| s == signal(i)));
+
+        // 延迟突然增加：开始重采样，回到目标后重新直通，切换处信号连续
+        write(200);
+        let mut corrected = false;
+        for _ in 0..3000 {
+            pull(&mut source, &mut output);
+            write(256);
+            corrected |= source.resampling;
+        }
+        assert!(corrected);
+        assert!(!source.resampling);
+        let max_step = output
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(max_step < 0.02, "{max_step}");
+    }
+}
@@ -0,0 +1,322 @@
+use super::error::AudioError;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// 预读时间：增益在峰值到来之前的这段时间内平滑下降
+const LOOKAHEAD_MS: f32 = 5.0;
+/// 参与真峰值估计的帧数：当前帧前后各两帧
+const WINDOW: usize = 5;
+/// 两帧之间估计采样点间峰值的插值位置（4 倍过采样）
+const OVERSAMPLE_POINTS: [f32; 3] = [0.25, 0.5, 0.75];
+
+/// 输出限制器参数
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimiterSettings {
+    pub enabled: bool,
+    /// 超过此电平开始压缩
+    pub threshold_db: f32,
+    /// 真峰值上限，输出不会超过此电平
+    pub ceiling_db: f32,
+    pub release_ms: f32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_db: -3.0,
+            ceiling_db: -1.0,
+            release_ms: 50.0,
+        }
+    }
+}
+
+impl LimiterSettings {
+    /// 限制器引入的延迟（帧）：预读时间加真峰值估计窗口的一半；关闭时为 0
+    pub fn latency_frames(&self, sample_rate: u32) -> usize {
+        if self.enabled {
+            lookahead_frames(sample_rate) + WINDOW / 2
+        } else {
+            0
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AudioError> {
+        if self.ceiling_db > 0.0 {
+            return Err(AudioError::Config(format!(
+                "Limiter ceiling must be at most 0 dB, got {}",
+                self.ceiling_db
+            )));
+        }
+        if self.threshold_db > self.ceiling_db {
+            return Err(AudioError::Config(format!(
+                "Limiter threshold {} dB is above the ceiling {} dB",
+                self.threshold_db, self.ceiling_db
+            )));
+        }
+        if self.release_ms <= 0.0 {
+            return Err(AudioError::Config(format!(
+                "Limiter release must be positive, got {} ms",
+                self.release_ms
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 带预读的真峰值限制器。
+///
+/// 用 Catmull-Rom 插值估计采样点之间的峰值，按阈值和上限计算所需增益：
+/// 阈值以下不处理，阈值到上限之间用 tanh 曲线平滑压缩。所需增益先在
+/// 预读窗口内取最小值，再做同样长度的滑动平均，使增益在峰值到达前线性下降，
+/// 峰值处的增益不高于所需增益；峰值过后按释放时间恢复。
+/// 输出比输入延迟预读时间加两帧；关闭时直接透传，不引入延迟。
+pub struct Limiter {
+    channels: usize,
+    sample_rate: u32,
+    settings: LimiterSettings,
+    threshold: f32,
+    ceiling: f32,
+    release: f32,
+    lookahead: usize,
+    /// 每个声道最近的 WINDOW 帧，用于估计中间一帧的真峰值
+    history: Vec<[f32; WINDOW]>,
+    /// 等待输出的样本（交错格式）
+    delay: VecDeque<f32>,
+    /// 滑动窗口最小值：(帧序号, 所需增益)，增益单调递增
+    minimum: VecDeque<(u64, f32)>,
+    /// 释放后的增益，滑动平均的输入
+    smoothing: VecDeque<f32>,
+    smoothing_sum: f64,
+    released: f32,
+    frame: u64,
+    max_reduction: f32,
+}
+
+impl Limiter {
+    pub fn new(channels: u16, sample_rate: u32, settings: LimiterSettings) -> Self {
+        let channels = channels.max(1) as usize;
+        let lookahead = lookahead_frames(sample_rate);
+        let mut limiter = Self {
+            channels,
+            sample_rate,
+            settings,
+            threshold: 1.0,
+            ceiling: 1.0,
+            release: 0.0,
+            lookahead,
+            history: vec![[0.0; WINDOW]; channels],
+            delay: Self::delay_line(lookahead, channels),
+            minimum: VecDeque::with_capacity(lookahead + 2),
+            smoothing: VecDeque::from(vec![1.0; lookahead]),
+            smoothing_sum: lookahead as f64,
+            released: 1.0,
+            frame: 0,
+            max_reduction: 1.0,
+        };
+        limiter.apply_settings(settings);
+        limiter
+    }
+
+    /// 预填充 lookahead 帧静音的延迟线。process 每帧先写入一帧再取出一帧，
+    /// 多预留一帧的容量，音频线程中不会扩容
+    fn delay_line(lookahead: usize, channels: usize) -> VecDeque<f32> {
+        let mut delay = VecDeque::with_capacity(lookahead * channels + channels);
+        delay.resize(lookahead * channels, 0.0);
+        delay
+    }
+
+    /// 参数变化时更新系数；只有开关变化时才重置延迟线
+    pub fn set_settings(&mut self, settings: LimiterSettings) {
+        if settings == self.settings {
+            return;
+        }
+        if settings.enabled != self.settings.enabled {
+            self.reset();
+        }
+        self.apply_settings(settings);
+    }
+
+    fn reset(&mut self) {
+        for history in &mut self.history {
+            *history = [0.0; WINDOW];
+        }
+        self.delay.clear();
+        self.delay.resize(self.lookahead * self.channels, 0.0);
+        self.minimum.clear();
+        self.smoothing.clear();
+        self.smoothing.resize(self.lookahead, 1.0);
+        self.smoothing_sum = self.lookahead as f64;
+        self.released = 1.0;
+    }
+
+    fn apply_settings(&mut self, settings: LimiterSettings) {
+        self.settings = settings;
+        self.ceiling = db_to_linear(settings.ceiling_db.min(0.0));
+        self.threshold = db_to_linear(settings.threshold_db).min(self.ceiling);
+        self.release =
+            (-1.0 / (settings.release_ms.max(1.0) / 1000.0 * self.sample_rate as f32)).exp();
+    }
+
+    /// 返回并清零上次读取以来的最大增益衰减（dB，正数）
+    pub fn take_gain_reduction_db(&mut self) -> f32 {
+        let reduction = -20.0 * self.max_reduction.log10();
+        self.max_reduction = 1.0;
+        reduction
+    }
+
+    /// 原地处理交错格式的样本
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        for frame in samples.chunks_exact_mut(self.channels) {
+            let mut peak = 0.0f32;
+            for (history, &sample) in self.history.iter_mut().zip(frame.iter()) {
+                history.rotate_left(1);
+                history[WINDOW - 1] = sample;
+                peak = peak.max(window_peak(history));
+                self.delay.push_back(history[WINDOW / 2]);
+            }
+
+            let required = self.required_gain(peak);
+            let gain = self.next_gain(required);
+            self.max_reduction = self.max_reduction.min(gain);
+
+            for sample in frame.iter_mut() {
+                *sample = self.delay.pop_front().unwrap_or(0.0) * gain;
+            }
+        }
+    }
+
+    fn required_gain(&self, peak: f32) -> f32 {
+        if peak <= self.threshold {
+            return 1.0;
+        }
+        let knee = self.ceiling - self.threshold;
+        let level = if knee > 0.0 {
+            self.threshold + knee * ((peak - self.threshold) / knee).tanh()
+        } else {
+            self.ceiling
+        };
+        level / peak
+    }
+
+    fn next_gain(&mut self, required: f32) -> f32 {
+        // 预读窗口（lookahead + 1 帧）内的最小所需增益
+        while self.minimum.back().is_some_and(|&(_, g)| g >= required) {
+            self.minimum.pop_back();
+        }
+        self.minimum.push_back((self.frame, required));
+        while self
+            .minimum
+            .front()
+            .is_some_and(|&(frame, _)| frame + (self.lookahead as u64) < self.frame)
+        {
+            self.minimum.pop_front();
+        }
+        self.frame += 1;
+        let hold = self.minimum.front().map_or(1.0, |&(_, g)| g);
+
+        self.released = if hold < self.released {
+            hold
+        } else {
+            hold + (self.released - hold) * self.release
+        };
+
+        self.smoothing_sum += self.released as f64;
+        self.smoothing.push_back(self.released);
+        if let Some(old) = self.smoothing.pop_front() {
+            self.smoothing_sum -= old as f64;
+        }
+        (self.smoothing_sum / self.lookahead as f64) as f32
+    }
+}
+
+/// 中间一帧及其两侧插值点的最大绝对值
+fn window_peak(h: &[f32; WINDOW]) -> f32 {
+    let mut peak = h[2].abs();
+    for t in OVERSAMPLE_POINTS {
+        peak = peak
+            .max(catmull_rom(h[0], h[1], h[2], h[3], t).abs())
+            .max(catmull_rom(h[1], h[2], h[3], h[4], t).abs());
+    }
+    peak
+}
+
+/// 在 p1 和 p2 之间插值
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+fn lookahead_frames(sample_rate: u32) -> usize {
+    ((LOOKAHEAD_MS / 1000.0 * sample_rate as f32) as usize).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_holds_true_peak_ceiling() {
+        let settings = LimiterSettings::default();
+        let mut limiter = Limiter::new(1, 48000, settings);
+        let mut samples: Vec<f32> = (0..4800).map(|i| 1.8 * (i as f32 * 0.05).sin()).collect();
+        // 静音之后突然出现的满幅脉冲由预读提前压低
+        samples.extend([0.0; 2400]);
+        samples.push(1.0);
+        samples.extend([0.0; 2400]);
+        limiter.process(&mut samples);
+
+        let ceiling = db_to_linear(settings.ceiling_db);
+        assert!(samples.iter().all(|s| s.abs() <= ceiling + 1e-4));
+        assert!(limiter.take_gain_reduction_db() > 5.0);
+        assert_eq!(limiter.take_gain_reduction_db(), 0.0);
+    }
+
+    #[test]
+    fn test_limiter_passes_quiet_signal_after_lookahead() {
+        let mut limiter = Limiter::new(1, 48000, LimiterSettings::default());
+        let capacity = limiter.delay.capacity();
+        let delay = LimiterSettings::default().latency_frames(48000);
+        assert_eq!(delay, 240 + 2);
+        let mut samples = vec![0.0; delay + 3];
+        samples[..3].copy_from_slice(&[0.1, 0.2, 0.3]);
+        limiter.process(&mut samples);
+        assert_eq!(samples[delay..], [0.1, 0.2, 0.3]);
+        assert_eq!(limiter.delay.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_disabled_limiter_passes_through() {
+        let mut limiter = Limiter::new(1, 48000, LimiterSettings::default());
+        limiter.set_settings(LimiterSettings {
+            enabled: false,
+            ..LimiterSettings::default()
+        });
+        let mut samples = [1.5, -1.5];
+        limiter.process(&mut samples);
+        assert_eq!(samples, [1.5, -1.5]);
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let settings = LimiterSettings {
+            threshold_db: 0.0,
+            ceiling_db: -1.0,
+            ..LimiterSettings::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+}
@@ -1,43 +1,43 @@
-pub struct AudioMixer {
-    buffer_size: usize,
-}
-
-impl AudioMixer {
-    pub fn new() -> Self {
-        Self { buffer_size: 512 }
-    }
+use super::AudioError;
+use serde::{Deserialize, Serialize};
 
-    pub fn mix(&self, inputs: &[&[f32]], output: &mut [f32]) {
-        output.iter_mut().for_each(|s| *s = 0.0);
+/// 输出设备上多条路由的混合方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MixMode {
+    /// 直接求和，由输出限制器防止过载
+    Sum,
+    /// 求和后固定衰减
+    Headroom { headroom_db: f32 },
+    /// 按路由数的平方根归一化，不相关信号的总响度保持不变
+    EqualPower,
+    /// 按路由数平均
+    #[default]
+    Average,
+}
 
-        for input in inputs {
-            for (i, &sample) in input.iter().enumerate() {
-                if i < output.len() {
-                    output[i] += sample;
-                }
+impl MixMode {
+    /// 检查参数是否有效：Headroom 的衰减量不能为负数
+    pub fn validate(&self) -> Result<(), AudioError> {
+        if let MixMode::Headroom { headroom_db } = self {
+            if !headroom_db.is_finite() || *headroom_db < 0.0 {
+                return Err(AudioError::Config(format!(
+                    "Mix headroom must be a non-negative number of dB, got {}",
+                    headroom_db
+                )));
             }
         }
-
-        let num_inputs = inputs.len().max(1) as f32;
-        for sample in output.iter_mut() {
-            *sample = (*sample / num_inputs).clamp(-1.0, 1.0);
-        }
+        Ok(())
     }
 
-    pub fn db_to_linear(&self, db: f32) -> f32 {
-        10.0_f32.powf(db / 20.0)
-    }
-
-    pub fn linear_to_db(&self, linear: f32) -> f32 {
-        if linear <= 0.0 {
-            return -f32::INFINITY;
+    /// 给定当前参与混合的路由数（可以是淡入淡出中的小数），返回求和后的增益
+    pub fn gain(&self, sources: f32) -> f32 {
+        let sources = sources.max(1.0);
+        match self {
+            MixMode::Sum => 1.0,
+            MixMode::Headroom { headroom_db } => 10.0_f32.powf(-headroom_db / 20.0),
+            MixMode::EqualPower => 1.0 / sources.sqrt(),
+            MixMode::Average => 1.0 / sources,
         }
-        20.0 * linear.log10()
-    }
-}
-
-impl Default for AudioMixer {
-    fn default() -> Self {
-        Self::new()
     }
 }
@@ -1,8 +1,28 @@
+pub mod backend;
+pub mod channels;
 mod device;
+mod drift;
 pub mod engine;
 pub mod error;
+pub mod fanout;
+mod graph;
+pub mod limiter;
 pub mod mixer;
+pub mod offline;
+pub mod recovery;
+pub mod resampler;
+pub mod ring_buffer;
+pub mod sample_format;
+pub mod watcher;
 
-pub use device::{DeviceInfo, DeviceManager};
-pub use engine::{AudioEngine, Route};
+pub use backend::{AudioBackend, CpalBackend, VirtualBackend, VirtualDevice};
+pub use device::{DeviceCapabilities, DeviceConfig, DeviceInfo, DeviceManager};
+pub(crate) use device::match_device;
+pub use engine::{AudioEngine, EngineState, EngineStatus, Route};
 pub use error::AudioError;
+pub use limiter::LimiterSettings;
+pub use mixer::MixMode;
+pub use offline::render_offline;
+pub use recovery::{DeviceFailure, RecoveryMonitor, StreamHealth};
+pub use sample_format::SampleFormat;
+pub use watcher::{DeviceEvent, DeviceSnapshot, DeviceWatcher};
@@ -0,0 +1,240 @@
+use super::{
+    backend::{VirtualBackend, VirtualDevice},
+    engine::{AudioEngine, Route},
+    error::AudioError,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+struct WavInput {
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<f32>,
+}
+
+impl WavInput {
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    fn duration_secs(&self) -> f64 {
+        self.frames() as f64 / self.sample_rate as f64
+    }
+}
+
+/// 离线渲染：用 WAV 文件代替输入设备，按路由配置运行引擎，
+/// 为每个输出设备写出一个 32 位浮点 WAV 文件。
+///
+/// 内部使用 VirtualBackend 手动驱动时钟，走的是与实时播放完全相同的
+/// 混音路径（包括输出限制器），但不受实时速度限制。输出设备的采样率和声道数取自
+/// 第一个路由到该输出的输入文件。
+pub fn render_offline(
+    routes: &[Route],
+    inputs: &HashMap<String, PathBuf>,
+    output_dir: &Path,
+) -> Result<HashMap<String, PathBuf>, AudioError> {
+    let backend = VirtualBackend::new();
+
+    let mut wavs = HashMap::new();
+    for (device_id, path) in inputs {
+        wavs.insert(device_id.clone(), read_wav(path)?);
+    }
+
+    let mut output_formats: HashMap<String, (u32, u16)> = HashMap::new();
+    for route in routes.iter().filter(|r| r.enabled) {
+        let wav = wavs
+            .get(&route.input_device_id)
+            .ok_or_else(|| AudioError::DeviceNotFound(route.input_device_id.clone()))?;
+        output_formats
+            .entry(route.output_device_id.clone())
+            .or_insert((wav.sample_rate, wav.channels));
+    }
+
+    let duration_secs = wavs
+        .values()
+        .map(WavInput::duration_secs)
+        .fold(0.0, f64::max);
+
+    for (device_id, wav) in wavs {
+        backend.add_device(VirtualDevice::input(
+            &device_id,
+            wav.sample_rate,
+            wav.channels,
+        ));
+
+        let mut position = 0;
+        let samples = wav.samples;
+        backend.set_source(
+            &device_id,
+            Box::new(move |block: &mut [f32]| {
+                for sample in block.iter_mut() {
+                    *sample = samples.get(position).copied().unwrap_or(0.0);
+                    position += 1;
+                }
+            }),
+        )?;
+    }
+
+    for (device_id, &(sample_rate, channels)) in &output_formats {
+        backend.add_device(VirtualDevice::output(device_id, sample_rate, channels));
+    }
+
+    // 所有虚拟设备共享同一个时钟，不存在漂移
+    let mut engine = AudioEngine::with_backend(Arc::new(backend.clone()));
+    engine.set_drift_compensation(false);
+    // 渲染期间路由不变，文件开头不需要淡入
+    engine.set_ramp_ms(0.0);
+    for route in routes {
+        engine.add_route(route.clone())?;
+    }
+    let status = engine.start()?;
+    if let Some(failure) = status.health.failed_devices.first() {
+        return Err(AudioError::Device(format!(
+            "{}: {}",
+            failure.device_id, failure.error
+        )));
+    }
+
+    // 限制器与实时输出一样工作，它的预读让输出整体后移：多渲染这段延迟，
+    // 再去掉文件开头的延迟部分
+    let latencies: HashMap<String, usize> = output_formats
+        .iter()
+        .map(|(device_id, &(sample_rate, _))| {
+            let latency = engine
+                .limiter_settings(device_id)
+                .latency_frames(sample_rate);
+            (device_id.clone(), latency)
+        })
+        .collect();
+    let latency_secs = output_formats
+        .iter()
+        .map(|(device_id, &(sample_rate, _))| latencies[device_id] as f64 / sample_rate as f64)
+        .fold(0.0, f64::max);
+
+    let block_secs = backend.block_duration().as_secs_f64();
+    let blocks = ((duration_secs + latency_secs) / block_secs).ceil() as usize;
+    for _ in 0..blocks {
+        backend.process_block();
+    }
+    engine.stop()?;
+
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut rendered = HashMap::new();
+    for (device_id, (sample_rate, channels)) in output_formats {
+        let mut samples = backend.take_output(&device_id);
+        samples.drain(..(latencies[&device_id] * channels as usize).min(samples.len()));
+        let frames = (duration_secs * sample_rate as f64).round() as usize;
+        samples.truncate(frames * channels as usize);
+
+        let path = output_dir.join(format!("{}.wav", sanitize_file_name(&device_id)));
+        write_wav(&path, sample_rate, channels, &samples)?;
+        rendered.insert(device_id, path);
+    }
+
+    Ok(rendered)
+}
+
+fn read_wav(path: &Path) -> Result<WavInput, AudioError> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    Ok(WavInput {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        samples,
+    })
+}
+
+fn write_wav(
+    path: &Path,
+    sample_rate: u32,
+    channels: u16,
+    samples: &[f32],
+) -> Result<(), AudioError> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn sanitize_file_name(device_id: &str) -> String {
+    device_id
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::limiter::LimiterSettings;
+
+    #[test]
+    fn test_render_offline_mixes_inputs_into_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let mic = dir.path().join("mic.wav");
+        let music = dir.path().join("music.wav");
+        write_wav(&mic, 48000, 2, &vec![0.25; 48000 * 2 / 10]).unwrap();
+        write_wav(&music, 48000, 2, &vec![0.75; 48000 * 2 / 20]).unwrap();
+
+        let routes = vec![Route::new("mic", "mix"), Route::new("music", "mix")];
+        let inputs = HashMap::from([("mic".to_string(), mic), ("music".to_string(), music)]);
+
+        let rendered = render_offline(&routes, &inputs, &dir.path().join("out")).unwrap();
+        let output = read_wav(&rendered["mix"]).unwrap();
+
+        assert_eq!(output.sample_rate, 48000);
+        assert_eq!(output.frames(), 4800);
+        assert!((output.samples[0] - 0.5).abs() < 1e-6);
+        assert!((output.samples[output.samples.len() - 1] - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_render_offline_limits_like_a_live_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let loud = dir.path().join("loud.wav");
+        write_wav(&loud, 48000, 1, &vec![1.5; 4800]).unwrap();
+
+        let routes = vec![Route::new("loud", "speakers")];
+        let inputs = HashMap::from([("loud".to_string(), loud)]);
+        let rendered = render_offline(&routes, &inputs, &dir.path().join("out")).unwrap();
+        let output = read_wav(&rendered["speakers"]).unwrap();
+
+        // 去掉限制器延迟后长度不变，开头没有静音，整个文件不超过上限
+        let ceiling = 10.0_f32.powf(LimiterSettings::default().ceiling_db / 20.0);
+        assert_eq!(output.frames(), 4800);
+        assert!(output.samples[0] > 0.5);
+        assert!(output.samples.iter().all(|&s| s <= ceiling + 1e-4));
+        assert!(output.samples[4799] > 0.8);
+    }
+}
@@ -0,0 +1,89 @@
+use super::engine::{AudioEngine, EngineStatus, Route};
+use crate::poller::Poller;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 设备出错后第一次重新打开前的等待时间，之后每次失败加倍
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+/// 重试间隔的上限
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// 默认的流状态检查间隔
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 一个无法打开或运行中出错的设备的重试状态
+#[derive(Clone, Debug)]
+pub(crate) struct Failure {
+    pub error: String,
+    pub attempts: u32,
+    pub next_retry: Instant,
+}
+
+impl Failure {
+    /// 记录又一次失败，按指数退避安排下一次重试
+    pub fn record(previous: Option<&Failure>, error: String, base_delay: Duration) -> Self {
+        let attempts = previous.map_or(0, |f| f.attempts) + 1;
+        let delay = base_delay
+            .saturating_mul(1 << (attempts - 1).min(16))
+            .min(MAX_RETRY_DELAY);
+        Self {
+            error,
+            attempts,
+            next_retry: Instant::now() + delay,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.next_retry <= Instant::now()
+    }
+}
+
+/// 报告给前端的设备故障
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceFailure {
+    pub device_id: String,
+    pub error: String,
+    /// 连续失败的次数
+    pub attempts: u32,
+    /// 距下一次重试的时间
+    pub retry_in_ms: u64,
+}
+
+/// 流的健康状态：出错的设备以及因此无法工作的路由
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StreamHealth {
+    pub failed_devices: Vec<DeviceFailure>,
+    pub degraded_routes: Vec<Route>,
+}
+
+/// 后台线程，定期让引擎处理流错误并重试出错的设备；
+/// 状态变化时回调最新的引擎状态。drop 时停止线程
+pub struct RecoveryMonitor {
+    poller: Poller,
+}
+
+impl RecoveryMonitor {
+    pub fn spawn<F>(engine: Arc<Mutex<AudioEngine>>, interval: Duration, mut on_change: F) -> Self
+    where
+        F: FnMut(EngineStatus) + Send + 'static,
+    {
+        let poller = Poller::spawn("stream-recovery", interval, move || {
+            let status = {
+                let Ok(mut engine) = engine.lock() else {
+                    return false;
+                };
+                engine.recover_streams().then(|| engine.status())
+            };
+            if let Some(status) = status {
+                on_change(status);
+            }
+            true
+        });
+
+        Self { poller }
+    }
+
+    pub fn stop(&mut self) {
+        self.poller.stop();
+    }
+}
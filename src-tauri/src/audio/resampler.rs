@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// 每侧的 sinc 零点数，决定带限插值的质量和延迟
+const SINC_ZERO_CROSSINGS: usize = 16;
+/// sinc 查找表每个单位的采样点数
+const SINC_TABLE_RESOLUTION: usize = 512;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleQuality {
+    /// 线性插值：CPU 占用最低，高频有混叠
+    Linear,
+    /// 加窗 sinc 带限插值
+    #[default]
+    Sinc,
+}
+
+/// 流式采样率转换器，处理交错格式的多声道数据。
+///
+/// 输入块可以是任意长度，转换结果追加到输出 Vec 中；
+/// 未消费完的输入帧保留在内部，供下一块继续使用。
+pub struct Resampler {
+    channels: usize,
+    /// 输入采样率 / 输出采样率
+    ratio: f64,
+    /// 截止频率（相对输入奈奎斯特频率），降采样时 < 1
+    cutoff: f64,
+    /// 插值核在输入帧上的半径
+    radius: f64,
+    kernel: Option<&'static [f32]>,
+    buffer: Vec<f32>,
+    /// 下一个输出帧在 buffer 中的位置（以输入帧为单位）
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(quality: ResampleQuality, input_rate: u32, output_rate: u32, channels: u16) -> Self {
+        let ratio = input_rate as f64 / output_rate as f64;
+        let channels = channels.max(1) as usize;
+
+        let (cutoff, radius, kernel) = match quality {
+            ResampleQuality::Linear => (1.0, 1.0, None),
+            ResampleQuality::Sinc => {
+                let cutoff = (1.0 / ratio).min(1.0);
+                let radius = SINC_ZERO_CROSSINGS as f64 / cutoff;
+                (cutoff, radius, Some(sinc_table()))
+            }
+        };
+
+        // 预填充静音作为历史数据，使第一个输出帧就有完整的插值窗口
+        let history = radius.ceil() as usize;
+
+        Self {
+            channels,
+            ratio,
+            cutoff,
+            radius,
+            kernel,
+            buffer: vec![0.0; history * channels],
+            position: history as f64,
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// 调整转换比例（输入采样率 / 输出采样率），用于时钟漂移补偿
+    pub fn set_ratio(&mut self, ratio: f64) {
+        self.ratio = ratio;
+    }
+
+    /// 每次最多输入 input_frames 帧时，内部缓冲区最多保存的帧数
+    pub fn buffer_frames(&self, input_frames: usize) -> usize {
+        self.radius.ceil() as usize * 2 + 2 + input_frames
+    }
+
+    /// 预留每次最多处理 input_frames 帧输入所需的内部缓冲区，避免 process 中扩容
+    pub fn reserve(&mut self, input_frames: usize) {
+        let frames = self.buffer_frames(input_frames);
+        self.buffer
+            .reserve((frames * self.channels).saturating_sub(self.buffer.len()));
+    }
+
+    /// 从下一块输入的第一帧开始输出，不插入静音历史，
+    /// 用于从直通切换到重采样时保持信号连续；缺少的左侧历史按截断的插值核处理
+    pub fn restart(&mut self) {
+        self.buffer.clear();
+        self.position = 0.0;
+    }
+
+    /// 把尚未输出的输入帧原样追加到 output（从最接近的整数位置开始），
+    /// 用于从重采样切换回直通
+    pub fn drain_input(&mut self, output: &mut Vec<f32>) {
+        let start = (self.position.round() as usize * self.channels).min(self.buffer.len());
+        output.extend_from_slice(&self.buffer[start..]);
+        self.restart();
+    }
+
+    pub fn reset(&mut self) {
+        let history = self.radius.ceil() as usize;
+        self.buffer.clear();
+        self.buffer.resize(history * self.channels, 0.0);
+        self.position = history as f64;
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        self.buffer.extend_from_slice(input);
+        let available_frames = self.buffer.len() / self.channels;
+
+        while self.position + self.radius < available_frames as f64 {
+            for channel in 0..self.channels {
+                let sample = match self.kernel {
+                    Some(table) => self.sinc_sample(table, channel),
+                    None => self.linear_sample(channel),
+                };
+                output.push(sample);
+            }
+            self.position += self.ratio;
+        }
+
+        // 丢弃不再需要的历史帧
+        let keep_from = (self.position - self.radius).floor().max(0.0) as usize;
+        if keep_from > 0 {
+            self.buffer.drain(..keep_from * self.channels);
+            self.position -= keep_from as f64;
+        }
+    }
+
+    fn frame_sample(&self, frame: usize, channel: usize) -> f32 {
+        self.buffer[frame * self.channels + channel]
+    }
+
+    fn linear_sample(&self, channel: usize) -> f32 {
+        let index = self.position.floor() as usize;
+        let frac = (self.position - index as f64) as f32;
+        let s1 = self.frame_sample(index, channel);
+        let s2 = self.frame_sample(index + 1, channel);
+        s1 * (1.0 - frac) + s2 * frac
+    }
+
+    fn sinc_sample(&self, table: &[f32], channel: usize) -> f32 {
+        let start = (self.position - self.radius).ceil().max(0.0) as usize;
+        let end = (self.position + self.radius).floor() as usize;
+
+        let mut sum = 0.0f32;
+        for frame in start..=end {
+            let distance = (self.position - frame as f64) * self.cutoff;
+            sum += self.frame_sample(frame, channel) * lookup_sinc(table, distance);
+        }
+        sum * self.cutoff as f32
+    }
+}
+
+/// 所有重采样器共用的 sinc 查找表
+fn sinc_table() -> &'static [f32] {
+    static TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+    TABLE.get_or_init(build_sinc_table)
+}
+
+/// 构建 Blackman 窗 sinc 查找表，覆盖 [0, SINC_ZERO_CROSSINGS]
+fn build_sinc_table() -> Vec<f32> {
+    let len = SINC_ZERO_CROSSINGS * SINC_TABLE_RESOLUTION + 1;
+    (0..len)
+        .map(|i| {
+            let x = i as f64 / SINC_TABLE_RESOLUTION as f64;
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let t = 0.5 + 0.5 * x / SINC_ZERO_CROSSINGS as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos();
+            (sinc * window) as f32
+        })
+        .collect()
+}
+
+fn lookup_sinc(table: &[f32], x: f64) -> f32 {
+    let pos = x.abs() * SINC_TABLE_RESOLUTION as f64;
+    let index = pos as usize;
+    if index + 1 >= table.len() {
+        return 0.0;
+    }
+    let frac = (pos - index as f64) as f32;
+    table[index] * (1.0 - frac) + table[index + 1] * frac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(rate: u32, freq: f64, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * freq * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn test_output_length_follows_ratio() {
+        for quality in [ResampleQuality::Linear, ResampleQuality::Sinc] {
+            let mut resampler = Resampler::new(quality, 48000, 44100, 2);
+            let mut output = Vec::new();
+            for _ in 0..100 {
+                resampler.process(&[0.0; 960], &mut output);
+            }
+            // 100 块 x 480 帧 @48k => ~44100 帧，减去插值窗口延迟
+            let frames = output.len() / 2;
+            assert!((44_050..=44_100).contains(&frames), "{frames}");
+        }
+    }
+
+    #[test]
+    fn test_sinc_table_is_shared() {
+        let a = Resampler::new(ResampleQuality::Sinc, 48000, 44100, 2);
+        let b = Resampler::new(ResampleQuality::Sinc, 44100, 48000, 1);
+        assert!(std::ptr::eq(a.kernel.unwrap(), b.kernel.unwrap()));
+    }
+
+    #[test]
+    fn test_sinc_preserves_sine_amplitude() {
+        let mut resampler = Resampler::new(ResampleQuality::Sinc, 48000, 44100, 1);
+        let mut output = Vec::new();
+        for block in sine(48000, 1000.0, 48000).chunks(480) {
+            resampler.process(block, &mut output);
+        }
+
+        let peak = output[4410..].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 1.0).abs() < 0.01, "{peak}");
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// 有界、无锁的单生产者单消费者环形缓冲区。
+///
+/// 输入回调是唯一的写入方，输出回调是唯一的读取方。样本以 f32 位模式
+/// 存放在原子变量中，因此不需要 unsafe。缓冲区满时丢弃新写入的样本，
+/// 而不是无限增长；整块丢弃保证多声道数据的帧对齐不被破坏。
+pub struct RingBuffer {
+    buffer: Box<[AtomicU32]>,
+    /// 单调递增的读写计数，取模后得到实际位置
+    write_pos: AtomicUsize,
+    read_pos: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+            write_pos: AtomicUsize::new(0),
+            read_pos: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn len(&self) -> usize {
+        let write = self.write_pos.load(Ordering::Acquire);
+        let read = self.read_pos.load(Ordering::Acquire);
+        write.wrapping_sub(read)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 获取缓冲区充满度（0.0 到 1.0）
+    pub fn fill_ratio(&self) -> f32 {
+        self.len() as f32 / self.capacity() as f32
+    }
+
+    /// 因缓冲区满而丢弃的样本总数
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 写入一块样本（仅生产者调用）。空间不足时整块丢弃，返回是否写入
+    pub fn write(&self, samples: &[f32]) -> bool {
+        let write = self.write_pos.load(Ordering::Relaxed);
+        let read = self.read_pos.load(Ordering::Acquire);
+        let free = self.capacity() - write.wrapping_sub(read);
+
+        if samples.len() > free {
+            self.dropped.fetch_add(samples.len(), Ordering::Relaxed);
+            return false;
+        }
+
+        for (i, &sample) in samples.iter().enumerate() {
+            let index = write.wrapping_add(i) % self.capacity();
+            self.buffer[index].store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.write_pos
+            .store(write.wrapping_add(samples.len()), Ordering::Release);
+        true
+    }
+
+    /// 读取样本（仅消费者调用），返回实际读取的数量
+    pub fn read(&self, samples: &mut [f32]) -> usize {
+        let read = self.read_pos.load(Ordering::Relaxed);
+        let write = self.write_pos.load(Ordering::Acquire);
+        let count = samples.len().min(write.wrapping_sub(read));
+
+        for (i, sample) in samples[..count].iter_mut().enumerate() {
+            let index = read.wrapping_add(i) % self.capacity();
+            *sample = f32::from_bits(self.buffer[index].load(Ordering::Relaxed));
+        }
+        self.read_pos
+            .store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// 丢弃所有未读样本（仅消费者调用）
+    pub fn clear(&self) {
+        let write = self.write_pos.load(Ordering::Acquire);
+        self.read_pos.store(write, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_wraps_around() {
+        let buffer = RingBuffer::new(4);
+        let mut output = [0.0; 3];
+
+        assert!(buffer.write(&[0.1, 0.2, 0.3]));
+        assert_eq!(buffer.read(&mut output), 3);
+        assert!(buffer.write(&[0.4, 0.5, 0.6]));
+        assert_eq!(buffer.read(&mut output), 3);
+        assert_eq!(output, [0.4, 0.5, 0.6]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_ring_buffer_is_bounded() {
+        let buffer = RingBuffer::new(4);
+        assert!(buffer.write(&[0.5; 3]));
+        assert!(!buffer.write(&[0.5; 3]));
+        assert_eq!(buffer.dropped(), 3);
+        assert_eq!(buffer.fill_ratio(), 0.75);
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+/// 设备原生样本格式。引擎内部统一使用 f32，在设备回调边界转换
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleFormat {
+    #[default]
+    F32,
+    I16,
+    I32,
+    U16,
+    U8,
+}
+
+/// 设备样本类型与 f32 之间的转换
+pub trait DeviceSample: Copy + Send + 'static {
+    /// 整数格式的量化步长（相对满幅 1.0），浮点格式为 None
+    const LSB: Option<f32>;
+
+    fn to_f32(self) -> f32;
+
+    /// 限幅并四舍五入到最近的量化值
+    fn from_f32(sample: f32) -> Self;
+}
+
+impl DeviceSample for f32 {
+    const LSB: Option<f32> = None;
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(sample: f32) -> Self {
+        sample
+    }
+}
+
+impl DeviceSample for i16 {
+    const LSB: Option<f32> = Some(1.0 / 32768.0);
+
+    fn to_f32(self) -> f32 {
+        self as f32 / 32768.0
+    }
+
+    fn from_f32(sample: f32) -> Self {
+        (sample * 32768.0)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+impl DeviceSample for i32 {
+    const LSB: Option<f32> = Some(1.0 / 2_147_483_648.0);
+
+    fn to_f32(self) -> f32 {
+        (self as f64 / 2_147_483_648.0) as f32
+    }
+
+    fn from_f32(sample: f32) -> Self {
+        (sample as f64 * 2_147_483_648.0)
+            .round()
+            .clamp(i32::MIN as f64, i32::MAX as f64) as i32
+    }
+}
+
+impl DeviceSample for u16 {
+    const LSB: Option<f32> = Some(1.0 / 32768.0);
+
+    fn to_f32(self) -> f32 {
+        (self as f32 - 32768.0) / 32768.0
+    }
+
+    fn from_f32(sample: f32) -> Self {
+        (sample * 32768.0 + 32768.0)
+            .round()
+            .clamp(0.0, u16::MAX as f32) as u16
+    }
+}
+
+impl DeviceSample for u8 {
+    const LSB: Option<f32> = Some(1.0 / 128.0);
+
+    fn to_f32(self) -> f32 {
+        (self as f32 - 128.0) / 128.0
+    }
+
+    fn from_f32(sample: f32) -> Self {
+        (sample * 128.0 + 128.0).round().clamp(0.0, u8::MAX as f32) as u8
+    }
+}
+
+/// TPDF（三角分布）抖动源：两个 ±0.5 LSB 均匀随机数之和，
+/// 把量化误差变成与信号无关的白噪声，避免低电平信号的谐波失真
+pub struct Dither {
+    state: u32,
+}
+
+impl Dither {
+    pub fn new() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+
+    /// 返回一个幅度在 ±lsb 之内的三角分布抖动值
+    pub fn next(&mut self, lsb: f32) -> f32 {
+        (self.uniform() + self.uniform()) * lsb
+    }
+
+    /// [-0.5, 0.5) 均匀分布（xorshift32）
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+    }
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 设备输入样本转换为 f32，结果写入 output（覆盖原内容）
+pub fn convert_input<T: DeviceSample>(input: &[T], output: &mut Vec<f32>) {
+    output.clear();
+    output.extend(input.iter().map(|&s| s.to_f32()));
+}
+
+/// f32 转换为设备输出样本；整数格式加 TPDF 抖动后量化
+pub fn convert_output<T: DeviceSample>(input: &[f32], output: &mut [T], dither: &mut Dither) {
+    match T::LSB {
+        Some(lsb) => {
+            for (out, &sample) in output.iter_mut().zip(input) {
+                *out = T::from_f32(sample + dither.next(lsb));
+            }
+        }
+        None => {
+            for (out, &sample) in output.iter_mut().zip(input) {
+                *out = T::from_f32(sample);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_formats_round_trip_within_one_step() {
+        for sample in [-1.0f32, -0.5, 0.0, 0.25, 0.999] {
+            assert!((i16::from_f32(sample).to_f32() - sample).abs() <= i16::LSB.unwrap());
+            assert!((i32::from_f32(sample).to_f32() - sample).abs() <= 1e-6);
+            assert!((u16::from_f32(sample).to_f32() - sample).abs() <= u16::LSB.unwrap());
+            assert!((u8::from_f32(sample).to_f32() - sample).abs() <= u8::LSB.unwrap());
+        }
+        assert_eq!(u8::from_f32(0.0), 128);
+        assert_eq!(i16::from_f32(2.0), i16::MAX);
+    }
+
+    #[test]
+    fn test_dither_is_bounded_and_unbiased() {
+        let input = vec![0.3f32; 48000];
+        let mut output = vec![0u8; input.len()];
+        convert_output(&input, &mut output, &mut Dither::new());
+
+        let lsb = u8::LSB.unwrap();
+        let decoded: Vec<f32> = output.iter().map(|s| s.to_f32()).collect();
+        assert!(decoded.iter().all(|s| (s - 0.3).abs() <= 1.5 * lsb + 1e-6));
+        // 不加抖动时每个样本都会量化到同一个值，误差恒定
+        let mean = decoded.iter().sum::<f32>() / decoded.len() as f32;
+        assert!((mean - 0.3).abs() < 0.05 * lsb, "{mean}");
+    }
+}
@@ -0,0 +1,182 @@
+use super::backend::AudioBackend;
+use super::device::DeviceInfo;
+use super::AudioError;
+use crate::poller::Poller;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 默认的设备列表轮询间隔
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 设备列表的变化，作为 Tauri 事件发给前端
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeviceEvent {
+    Added {
+        device: DeviceInfo,
+    },
+    Removed {
+        device: DeviceInfo,
+    },
+    DefaultChanged {
+        is_input: bool,
+        device_id: Option<String>,
+    },
+}
+
+impl DeviceEvent {
+    /// 对应的 Tauri 事件名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Added { .. } => "device-added",
+            Self::Removed { .. } => "device-removed",
+            Self::DefaultChanged { .. } => "default-changed",
+        }
+    }
+}
+
+/// 某一时刻的设备列表和默认设备
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceSnapshot {
+    pub devices: Vec<DeviceInfo>,
+    pub default_input: Option<String>,
+    pub default_output: Option<String>,
+}
+
+impl DeviceSnapshot {
+    pub fn capture(backend: &dyn AudioBackend) -> Result<Self, AudioError> {
+        Ok(Self {
+            devices: backend.list_devices()?,
+            default_input: backend.default_device_id(true)?,
+            default_output: backend.default_device_id(false)?,
+        })
+    }
+
+    /// 从 self 变为 next 产生的事件：先移除，再添加，最后是默认设备变化
+    pub fn diff(&self, next: &DeviceSnapshot) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        for device in &self.devices {
+            if !next.devices.iter().any(|d| d.id == device.id) {
+                events.push(DeviceEvent::Removed {
+                    device: device.clone(),
+                });
+            }
+        }
+        for device in &next.devices {
+            if !self.devices.iter().any(|d| d.id == device.id) {
+                events.push(DeviceEvent::Added {
+                    device: device.clone(),
+                });
+            }
+        }
+        if self.default_input != next.default_input {
+            events.push(DeviceEvent::DefaultChanged {
+                is_input: true,
+                device_id: next.default_input.clone(),
+            });
+        }
+        if self.default_output != next.default_output {
+            events.push(DeviceEvent::DefaultChanged {
+                is_input: false,
+                device_id: next.default_output.clone(),
+            });
+        }
+        events
+    }
+}
+
+/// 后台设备监视线程，定期比较设备列表并回调变化。
+/// 大多数后端没有可移植的热插拔通知，因此采用轮询；drop 时停止线程
+pub struct DeviceWatcher {
+    poller: Poller,
+}
+
+impl DeviceWatcher {
+    pub fn spawn<F>(backend: Arc<dyn AudioBackend>, interval: Duration, mut on_event: F) -> Self
+    where
+        F: FnMut(DeviceEvent) + Send + 'static,
+    {
+        let mut current = DeviceSnapshot::capture(backend.as_ref()).unwrap_or_else(|e| {
+            tracing::warn!("Failed to list devices: {}", e);
+            DeviceSnapshot::default()
+        });
+        let poller = Poller::spawn("device-watcher", interval, move || {
+            let next = match DeviceSnapshot::capture(backend.as_ref()) {
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::warn!("Failed to list devices: {}", e);
+                    return true;
+                }
+            };
+            for event in current.diff(&next) {
+                tracing::info!("Device change: {:?}", event);
+                on_event(event);
+            }
+            current = next;
+            true
+        });
+
+        Self { poller }
+    }
+
+    pub fn stop(&mut self) {
+        self.poller.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::backend::{VirtualBackend, VirtualDevice};
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    #[test]
+    fn test_watcher_reports_hot_plug_and_default_changes() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = DeviceWatcher::spawn(
+            Arc::new(backend.clone()),
+            Duration::from_millis(5),
+            move |event| {
+                let _ = tx.send(event);
+            },
+        );
+        let next_event = || rx.recv_timeout(Duration::from_secs(2)).unwrap();
+
+        backend.add_device(VirtualDevice::output("headphones", 48000, 2));
+        assert!(matches!(
+            next_event(),
+            DeviceEvent::Added { device } if device.id == "headphones"
+        ));
+
+        backend.set_default_device("headphones").unwrap();
+        assert_eq!(
+            next_event(),
+            DeviceEvent::DefaultChanged {
+                is_input: false,
+                device_id: Some("headphones".into()),
+            }
+        );
+
+        // 默认设备被拔出时，默认设备回到 speakers
+        backend.remove_device("headphones");
+        let removed = next_event();
+        assert_eq!(removed.name(), "device-removed");
+        assert_eq!(
+            next_event(),
+            DeviceEvent::DefaultChanged {
+                is_input: false,
+                device_id: Some("speakers".into()),
+            }
+        );
+
+        let started = Instant::now();
+        watcher.stop();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
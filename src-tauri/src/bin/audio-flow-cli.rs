@@ -0,0 +1,485 @@
+//! 不带图形界面的 Audio Flow：使用同一套引擎和配置文件，
+//! 用于无显示器的机器和脚本
+
+use anyhow::{anyhow, bail, Context, Result};
+use audio_flow::audio::recovery::DEFAULT_CHECK_INTERVAL;
+use audio_flow::audio::{AudioEngine, DeviceInfo, EngineStatus, RecoveryMonitor, Route};
+use audio_flow::config::{AppConfig, ConfigEvent, ConfigStorage, CONFIG_POLL_INTERVAL};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: audio-flow-cli [--config-dir <dir>] [--verbose] <command> [args]
+
+Commands:
+  devices                              List input and output devices
+  profiles                             List profiles, the active one marked with *
+  routes                               Show the routes of the active profile
+  load <profile>                       Make <profile> the active profile
+  add <input> <output> [--gain <dB>]   Add a route to the active profile
+  remove <input> <output>              Remove a route from the active profile
+  run [--profile <name>] [--interval <ms>] [--duration <s>]
+                                       Start the engine and print meters until
+                                       interrupted or the duration has passed
+
+Devices can be given by id or by name. Changes are saved to the same
+configuration file as the desktop app.";
+
+/// 默认的电平表刷新间隔
+const DEFAULT_METER_INTERVAL: Duration = Duration::from_millis(500);
+/// 电平表显示的最低电平
+const METER_FLOOR_DB: f32 = -60.0;
+const METER_WIDTH: usize = 20;
+
+fn main() -> ExitCode {
+    let mut args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(if args.verbose {
+            tracing::Level::INFO
+        } else {
+            tracing::Level::WARN
+        })
+        .init();
+
+    match run(&mut args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &mut Args) -> Result<()> {
+    let storage = Arc::new(match args.option("config-dir") {
+        Some(dir) => ConfigStorage::with_dir(PathBuf::from(dir))?,
+        None => ConfigStorage::new(),
+    });
+    let command = args.positional(0, "command")?;
+
+    match command.as_str() {
+        "devices" => {
+            args.finish(1)?;
+            list_devices()
+        }
+        "profiles" => {
+            args.finish(1)?;
+            let list = storage.update(|config| Ok(config.profile_list()))?;
+            for name in list.names {
+                let marker = if name == list.active { '*' } else { ' ' };
+                println!("{} {}", marker, name);
+            }
+            Ok(())
+        }
+        "routes" => {
+            args.finish(1)?;
+            let config = load(&storage)?;
+            for route in &config.current.routes {
+                println!(
+                    "{} -> {}  {:+.1} dB{}",
+                    route.input_device_id,
+                    route.output_device_id,
+                    route.gain_db,
+                    if route.enabled { "" } else { "  (disabled)" }
+                );
+            }
+            Ok(())
+        }
+        "load" => {
+            let name = args.positional(1, "profile")?;
+            args.finish(2)?;
+            storage.update(|config| {
+                let current = config.current.clone();
+                config.activate_profile(&name, current).map(drop)
+            })?;
+            println!("Active profile: {}", name);
+            Ok(())
+        }
+        "add" => {
+            let input = args.positional(1, "input")?;
+            let output = args.positional(2, "output")?;
+            let gain_db = match args.option("gain") {
+                Some(gain) => gain
+                    .parse()
+                    .with_context(|| format!("invalid gain '{}'", gain))?,
+                None => 0.0,
+            };
+            args.finish(3)?;
+            let devices = AudioEngine::new().device_manager.list_devices()?;
+            let route = Route {
+                gain_db,
+                ..Route::new(
+                    &find_device(&devices, &input, true)?,
+                    &find_device(&devices, &output, false)?,
+                )
+            };
+            storage.update(|config| {
+                let mut profile = config.current.clone();
+                profile.routes.retain(|r| {
+                    !(r.input_device_id == route.input_device_id
+                        && r.output_device_id == route.output_device_id)
+                });
+                profile.routes.push(route.clone());
+                profile.validate()?;
+                config.store_current(profile);
+                Ok(())
+            })?;
+            println!(
+                "Added route {} -> {}",
+                route.input_device_id, route.output_device_id
+            );
+            Ok(())
+        }
+        "remove" => {
+            let input = args.positional(1, "input")?;
+            let output = args.positional(2, "output")?;
+            args.finish(3)?;
+            // 设备已拔出时仍可以用 id 删除路由
+            let devices = AudioEngine::new()
+                .device_manager
+                .list_devices()
+                .unwrap_or_default();
+            let input = find_device(&devices, &input, true).unwrap_or(input);
+            let output = find_device(&devices, &output, false).unwrap_or(output);
+            let removed = storage.update(|config| {
+                let mut profile = config.current.clone();
+                let before = profile.routes.len();
+                profile
+                    .routes
+                    .retain(|r| !(r.input_device_id == input && r.output_device_id == output));
+                let removed = profile.routes.len() < before;
+                config.store_current(profile);
+                Ok(removed)
+            })?;
+            if !removed {
+                bail!("no route {} -> {}", input, output);
+            }
+            println!("Removed route {} -> {}", input, output);
+            Ok(())
+        }
+        "run" => {
+            let profile = args.option("profile");
+            let interval = match args.option("interval") {
+                Some(ms) => Duration::from_millis(
+                    ms.parse()
+                        .with_context(|| format!("invalid interval '{}'", ms))?,
+                ),
+                None => DEFAULT_METER_INTERVAL,
+            };
+            let duration = args
+                .option("duration")
+                .map(|s| {
+                    s.parse::<f64>()
+                        .ok()
+                        .filter(|s| s.is_finite() && *s >= 0.0)
+                        .map(Duration::from_secs_f64)
+                        .ok_or_else(|| anyhow!("invalid duration '{}'", s))
+                })
+                .transpose()?;
+            args.finish(1)?;
+            run_engine(&storage, profile, interval, duration)
+        }
+        other => Err(anyhow!("unknown command '{}'", other)),
+    }
+}
+
+fn load(storage: &ConfigStorage) -> Result<AppConfig> {
+    let mut config = storage.load_config()?.unwrap_or_default();
+    config.normalize_profiles();
+    Ok(config)
+}
+
+fn list_devices() -> Result<()> {
+    let engine = AudioEngine::new();
+    let snapshot = engine.device_manager.snapshot()?;
+    let width = snapshot
+        .devices
+        .iter()
+        .map(|d| d.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for device in &snapshot.devices {
+        let is_default = [&snapshot.default_input, &snapshot.default_output]
+            .into_iter()
+            .any(|id| id.as_deref() == Some(device.id.as_str()));
+        println!(
+            "{:<6} {} {:<width$}  {:>6} Hz  {} ch  {}",
+            if device.is_input { "input" } else { "output" },
+            if is_default { '*' } else { ' ' },
+            device.name,
+            device.sample_rate,
+            device.channels,
+            device.id,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// 按 id 或名称（不区分大小写）查找设备
+fn find_device(devices: &[DeviceInfo], query: &str, is_input: bool) -> Result<String> {
+    let candidates: Vec<&DeviceInfo> = devices
+        .iter()
+        .filter(|d| if is_input { d.is_input } else { d.is_output })
+        .collect();
+    if let Some(device) = candidates.iter().find(|d| d.id == query) {
+        return Ok(device.id.clone());
+    }
+    let matches: Vec<&&DeviceInfo> = candidates
+        .iter()
+        .filter(|d| d.name.eq_ignore_ascii_case(query))
+        .collect();
+    let direction = if is_input { "input" } else { "output" };
+    match matches.as_slice() {
+        [device] => Ok(device.id.clone()),
+        [] => bail!("no {} device named '{}'", direction, query),
+        _ => bail!(
+            "several {} devices are named '{}', use the device id instead",
+            direction,
+            query
+        ),
+    }
+}
+
+fn run_engine(
+    storage: &Arc<ConfigStorage>,
+    profile: Option<String>,
+    interval: Duration,
+    duration: Option<Duration>,
+) -> Result<()> {
+    if let Some(name) = &profile {
+        storage.update(|config| {
+            let current = config.current.clone();
+            config.activate_profile(name, current).map(drop)
+        })?;
+    }
+    let config = load(storage)?;
+
+    let engine = Arc::new(Mutex::new(AudioEngine::new()));
+    let (status, names) = {
+        let mut engine = engine.lock().map_err(|e| anyhow!("{}", e))?;
+        // 无法打开的设备由引擎状态报告，其余路由照常运行
+        if let Err(e) = config.current.apply(&mut engine) {
+            eprintln!("warning: {}", e);
+        }
+        let names: HashMap<String, String> = engine
+            .device_manager
+            .list_devices()?
+            .into_iter()
+            .map(|d| (d.id, d.name))
+            .collect();
+        (engine.start()?, names)
+    };
+    println!(
+        "Profile '{}': {} routes",
+        config.active_profile,
+        config.current.routes.len()
+    );
+    print_status(&status);
+
+    let _monitor = RecoveryMonitor::spawn(engine.clone(), DEFAULT_CHECK_INTERVAL, |status| {
+        print_status(&status)
+    });
+    let _watcher = storage.watch(engine.clone(), CONFIG_POLL_INTERVAL, |event| match event {
+        ConfigEvent::Applied { diff, .. } => println!(
+            "Configuration reloaded: {} added, {} removed, {} changed routes",
+            diff.added_routes.len(),
+            diff.removed_routes.len(),
+            diff.changed_routes.len()
+        ),
+        ConfigEvent::Rejected { error } => eprintln!("Configuration rejected: {}", error),
+    });
+
+    let started = Instant::now();
+    while duration.is_none_or(|d| started.elapsed() < d) {
+        thread::sleep(interval);
+        let levels = match engine.lock() {
+            Ok(engine) => engine.get_peak_levels(),
+            Err(_) => break,
+        };
+        println!("{}", format_meters(&levels, &names));
+    }
+
+    if let Ok(mut engine) = engine.lock() {
+        engine.stop()?;
+    }
+    Ok(())
+}
+
+fn print_status(status: &EngineStatus) {
+    println!("Engine {:?}", status.state);
+    for failure in &status.health.failed_devices {
+        eprintln!(
+            "  {} failed ({} attempts, retrying in {} ms): {}",
+            failure.device_id, failure.attempts, failure.retry_in_ms, failure.error
+        );
+    }
+}
+
+/// 一行电平表，按设备名称排序
+fn format_meters(levels: &HashMap<String, f32>, names: &HashMap<String, String>) -> String {
+    let mut meters: Vec<(&str, f32)> = levels
+        .iter()
+        .map(|(id, peak)| (names.get(id).map_or(id.as_str(), String::as_str), *peak))
+        .collect();
+    meters.sort_by(|a, b| a.0.cmp(b.0));
+    meters
+        .into_iter()
+        .map(|(name, peak)| {
+            let db = 20.0 * peak.max(1e-6).log10();
+            let filled = (((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+                * METER_WIDTH as f32)
+                .round() as usize;
+            let level = if db <= METER_FLOOR_DB {
+                "  -inf".to_string()
+            } else {
+                format!("{:6.1}", db)
+            };
+            format!(
+                "{} {} dB [{}{}]",
+                name,
+                level,
+                "#".repeat(filled),
+                " ".repeat(METER_WIDTH - filled)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// 命令行参数：位置参数和 `--name value` 形式的选项
+#[derive(Debug, Default)]
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    help: bool,
+    verbose: bool,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-v" | "--verbose" => parsed.verbose = true,
+                _ => match arg.strip_prefix("--") {
+                    Some(name) => {
+                        let value = args
+                            .next()
+                            .ok_or_else(|| anyhow!("missing value for --{}", name))?;
+                        parsed.options.insert(name.to_string(), value);
+                    }
+                    None => parsed.positional.push(arg),
+                },
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn positional(&self, index: usize, name: &str) -> Result<String> {
+        self.positional
+            .get(index)
+            .cloned()
+            .ok_or_else(|| anyhow!("missing <{}>", name))
+    }
+
+    fn option(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name)
+    }
+
+    /// 检查没有多余的参数或不认识的选项
+    fn finish(&self, positional: usize) -> Result<()> {
+        if let Some(extra) = self.positional.get(positional) {
+            bail!("unexpected argument '{}'", extra);
+        }
+        if let Some(name) = self.options.keys().next() {
+            bail!("unknown option --{}", name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str, is_input: bool) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            backend_id: None,
+            is_input,
+            is_output: !is_input,
+            sample_rate: 48000,
+            channels: 2,
+            is_vb_cable: false,
+        }
+    }
+
+    #[test]
+    fn test_arguments_and_device_lookup() {
+        let mut args = Args::parse(
+            [
+                "--config-dir",
+                "/tmp/x",
+                "add",
+                "Mic",
+                "output:1",
+                "--gain",
+                "-3",
+            ]
+            .map(String::from),
+        )
+        .unwrap();
+        assert_eq!(args.option("config-dir").as_deref(), Some("/tmp/x"));
+        assert_eq!(args.positional(0, "command").unwrap(), "add");
+        assert!(args.finish(3).is_err());
+        assert_eq!(args.option("gain").as_deref(), Some("-3"));
+        args.finish(3).unwrap();
+        assert!(Args::parse(["run", "--duration"].map(String::from)).is_err());
+
+        let devices = [
+            device("input:1", "USB Mic", true),
+            device("output:1", "Speakers", false),
+            device("output:2", "Headset", false),
+            device("output:3", "Headset", false),
+        ];
+        assert_eq!(find_device(&devices, "usb mic", true).unwrap(), "input:1");
+        assert_eq!(
+            find_device(&devices, "output:3", false).unwrap(),
+            "output:3"
+        );
+        assert!(find_device(&devices, "Speakers", true).is_err());
+        assert!(find_device(&devices, "Headset", false).is_err());
+    }
+
+    #[test]
+    fn test_meters_are_sorted_by_device_name() {
+        let levels = HashMap::from([("output:1".to_string(), 1.0), ("input:1".to_string(), 0.0)]);
+        let names = HashMap::from([("input:1".to_string(), "Mic".to_string())]);
+        let line = format_meters(&levels, &names);
+        assert!(line.starts_with("Mic   -inf dB [  "), "{line}");
+        assert!(
+            line.ends_with("output:1    0.0 dB [####################]"),
+            "{line}"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::audio::AudioError;
+use crate::config::{self, AppConfig, ConfigBackup, ImportReport, Profile, ProfileList};
+use std::path::Path;
+use tauri::State;
+
+#[tauri::command]
+pub async fn save_config(state: State<'_, crate::AppState>) -> Result<(), String> {
+    state
+        .storage
+        .save_engine(&state.engine)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn load_config(state: State<'_, crate::AppState>) -> Result<AppConfig, String> {
+    let mut config = state
+        .storage
+        .load_config()
+        .map_err(|e| e.to_string())?
+        .ok_or("No saved configuration")?;
+    config.normalize_profiles();
+    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+    config.current.apply(&mut engine).map_err(|e| e.to_string())?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn reset_config(state: State<'_, crate::AppState>) -> Result<(), String> {
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        Profile::default().apply(&mut engine)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_profiles(state: State<'_, crate::AppState>) -> Result<ProfileList, String> {
+    state
+        .storage
+        .update(|config| Ok(config.profile_list()))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn create_profile(name: String, state: State<'_, crate::AppState>) -> Result<ProfileList, String> {
+    state
+        .storage
+        .update(|config| {
+            config.create_profile(&name)?;
+            Ok(config.profile_list())
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn duplicate_profile(source: String, name: String, state: State<'_, crate::AppState>) -> Result<ProfileList, String> {
+    let storage = &state.storage;
+    // 复制当前档案时包含尚未自动保存的修改
+    storage.save_engine(&state.engine).map_err(|e| e.to_string())?;
+    storage
+        .update(|config| {
+            config.duplicate_profile(&source, &name)?;
+            Ok(config.profile_list())
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn rename_profile(name: String, new_name: String, state: State<'_, crate::AppState>) -> Result<ProfileList, String> {
+    state
+        .storage
+        .update(|config| {
+            config.rename_profile(&name, &new_name)?;
+            Ok(config.profile_list())
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_profile(name: String, state: State<'_, crate::AppState>) -> Result<ProfileList, String> {
+    state
+        .storage
+        .update(|config| {
+            config.delete_profile(&name)?;
+            Ok(config.profile_list())
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn activate_profile(name: String, state: State<'_, crate::AppState>) -> Result<ProfileList, String> {
+    state
+        .storage
+        .update(|config| {
+            let mut engine = state
+                .engine
+                .lock()
+                .map_err(|e| AudioError::Config(e.to_string()))?;
+            let profile = config.activate_profile(&name, Profile::capture(&engine))?;
+            // 无法打开的设备由引擎状态报告，不影响切换
+            if let Err(e) = profile.apply(&mut engine) {
+                tracing::warn!("Profile '{}' activated with errors: {}", name, e);
+            }
+            Ok(config.profile_list())
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn export_config(path: String, profile: Option<String>, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let storage = &state.storage;
+    // 导出时包含尚未自动保存的修改
+    storage.save_engine(&state.engine).map_err(|e| e.to_string())?;
+    let config = storage
+        .update(|config| Ok(config.clone()))
+        .map_err(|e| e.to_string())?;
+    let devices = {
+        let engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.device_manager.list_devices().map_err(|e| e.to_string())?
+    };
+    let exported = config
+        .export(profile.as_deref(), &devices)
+        .map_err(|e| e.to_string())?;
+    config::export_config(Path::new(&path), &exported).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_config(path: String, activate: bool, state: State<'_, crate::AppState>) -> Result<ImportReport, String> {
+    let mut imported = config::import_config(Path::new(&path)).map_err(|e| e.to_string())?;
+    let devices = {
+        let engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.device_manager.list_devices().map_err(|e| e.to_string())?
+    };
+    // 先把设备 id 映射到本机设备，再加入档案或应用到引擎
+    let mut report = imported.remap_devices(&devices);
+    state
+        .storage
+        .update(|config| {
+            let (names, active) = config.import_profiles(imported);
+            report.profiles = names;
+            if activate {
+                let mut engine = state
+                    .engine
+                    .lock()
+                    .map_err(|e| AudioError::Config(e.to_string()))?;
+                let profile = config.activate_profile(&active, Profile::capture(&engine))?;
+                if let Err(e) = profile.apply(&mut engine) {
+                    tracing::warn!("Imported profile '{}' activated with errors: {}", active, e);
+                }
+            }
+            Ok(report)
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_config_backups(state: State<'_, crate::AppState>) -> Result<Vec<ConfigBackup>, String> {
+    state.storage.list_backups().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn restore_config_backup(name: String, state: State<'_, crate::AppState>) -> Result<AppConfig, String> {
+    let config = state
+        .storage
+        .restore_backup(&name)
+        .map_err(|e| e.to_string())?;
+    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+    config.current.apply(&mut engine).map_err(|e| e.to_string())?;
+    Ok(config)
+}
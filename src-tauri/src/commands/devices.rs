@@ -1,4 +1,4 @@
-use crate::audio::DeviceInfo;
+use crate::audio::{DeviceCapabilities, DeviceConfig, DeviceInfo, StreamHealth};
 use tauri::State;
 
 #[tauri::command]
@@ -14,3 +14,43 @@ pub async fn get_peak_levels(state: State<'_, crate::AppState>) -> Result<std::c
     let levels = engine.get_peak_levels();
     Ok(levels)
 }
+
+#[tauri::command]
+pub async fn get_gain_reduction(state: State<'_, crate::AppState>) -> Result<std::collections::HashMap<String, f32>, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    Ok(engine.get_gain_reduction())
+}
+
+#[tauri::command]
+pub async fn get_device_capabilities(device_id: String, state: State<'_, crate::AppState>) -> Result<Vec<DeviceCapabilities>, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    engine.device_manager.capabilities(&device_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_device_capabilities(state: State<'_, crate::AppState>) -> Result<Vec<DeviceCapabilities>, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    engine.device_manager.list_capabilities().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_device_config(device_id: String, config: DeviceConfig, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.set_device_config(&device_id, config)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_device_configs(state: State<'_, crate::AppState>) -> Result<std::collections::HashMap<String, DeviceConfig>, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    Ok(engine.get_device_configs())
+}
+
+#[tauri::command]
+pub async fn get_stream_health(state: State<'_, crate::AppState>) -> Result<StreamHealth, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    Ok(engine.stream_health())
+}
@@ -1,4 +1,6 @@
+mod config;
 mod devices;
 mod routing;
+pub use config::*;
 pub use devices::*;
 pub use routing::*;
@@ -1,26 +1,50 @@
-use crate::audio::engine::Route;
+use crate::audio::engine::{EngineStatus, Route};
+use crate::audio::{LimiterSettings, MixMode};
+use std::collections::HashMap;
 use tauri::State;
 
 #[tauri::command]
 pub async fn add_route(route: Route, state: State<'_, crate::AppState>) -> Result<(), String> {
-    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
-    engine.add_route(route).map_err(|e| e.to_string())
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.add_route(route)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn remove_route(input_id: String, output_id: String, state: State<'_, crate::AppState>) -> Result<(), String> {
-    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
-    engine.remove_route(&input_id, &output_id).map_err(|e| e.to_string())
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.remove_route(&input_id, &output_id)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_route_enabled(input_id: String, output_id: String, enabled: bool, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.set_route_enabled(&input_id, &output_id, enabled)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn set_gain(device_id: String, gain_db: f32, state: State<'_, crate::AppState>) -> Result<(), String> {
-    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
-    engine.set_gain(&device_id, gain_db).map_err(|e| e.to_string())
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.set_gain(&device_id, gain_db)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn start_engine(state: State<'_, crate::AppState>) -> Result<(), String> {
+pub async fn start_engine(state: State<'_, crate::AppState>) -> Result<EngineStatus, String> {
     let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
     engine.start().map_err(|e| e.to_string())
 }
@@ -31,8 +55,66 @@ pub async fn stop_engine(state: State<'_, crate::AppState>) -> Result<(), String
     engine.stop().map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn restart_engine(state: State<'_, crate::AppState>) -> Result<EngineStatus, String> {
+    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+    engine.restart().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_engine_status(state: State<'_, crate::AppState>) -> Result<EngineStatus, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    Ok(engine.status())
+}
+
 #[tauri::command]
 pub async fn get_routes(state: State<'_, crate::AppState>) -> Result<Vec<Route>, String> {
     let engine = state.engine.lock().map_err(|e| e.to_string())?;
     Ok(engine.get_routes())
 }
+
+#[tauri::command]
+pub async fn set_target_latency(target_ms: f32, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+    engine.set_target_latency_ms(target_ms);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_ramp_time(ramp_ms: f32, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+    engine.set_ramp_ms(ramp_ms);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_mix_mode(device_id: String, mode: MixMode, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.set_mix_mode(&device_id, mode)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_mix_modes(state: State<'_, crate::AppState>) -> Result<HashMap<String, MixMode>, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    Ok(engine.get_mix_modes())
+}
+
+#[tauri::command]
+pub async fn set_limiter(device_id: String, settings: LimiterSettings, state: State<'_, crate::AppState>) -> Result<(), String> {
+    let result = {
+        let mut engine = state.engine.lock().map_err(|e| e.to_string())?;
+        engine.set_limiter_settings(&device_id, settings)
+    };
+    state.config_changed();
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_limiters(state: State<'_, crate::AppState>) -> Result<HashMap<String, LimiterSettings>, String> {
+    let engine = state.engine.lock().map_err(|e| e.to_string())?;
+    Ok(engine.get_limiter_settings())
+}
@@ -0,0 +1,55 @@
+use super::storage::ConfigStorage;
+use crate::audio::{AudioEngine, AudioError};
+use crate::poller::Poller;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 最后一次修改之后等待多久再写入磁盘
+pub const AUTOSAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// 后台自动保存：每次修改调用 `notify`，连续修改合并为一次写入。
+/// drop 时立即写入尚未保存的修改
+pub struct Autosave {
+    poller: Poller,
+}
+
+impl Autosave {
+    pub fn spawn(
+        engine: Arc<Mutex<AudioEngine>>,
+        storage: Arc<ConfigStorage>,
+        delay: Duration,
+    ) -> Self {
+        let poller = Poller::spawn_with("config-autosave", move |changes| {
+            // 等待第一次修改，然后等到 delay 时间内没有新的修改
+            while changes.recv().is_ok() {
+                let disconnected = loop {
+                    match changes.recv_timeout(delay) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break false,
+                        Err(RecvTimeoutError::Disconnected) => break true,
+                    }
+                };
+
+                match storage.save_engine(&engine) {
+                    Ok(()) => tracing::debug!("Configuration saved"),
+                    Err(AudioError::ConfigPending) => tracing::info!(
+                        "Configuration file changed on disk, keeping it instead of the engine settings"
+                    ),
+                    Err(e) => tracing::error!("Failed to save configuration: {}", e),
+                }
+
+                if disconnected {
+                    break;
+                }
+            }
+        });
+
+        Self { poller }
+    }
+
+    /// 标记配置已修改
+    pub fn notify(&self) {
+        self.poller.notify();
+    }
+}
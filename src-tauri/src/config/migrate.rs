@@ -0,0 +1,143 @@
+use crate::audio::AudioError;
+use std::path::Path;
+use toml::{Table, Value};
+
+/// 当前的配置文件版本。
+///
+/// - 1：没有 version 字段的最初格式，只有一套路由和设备设置
+/// - 2：增加配置档案
+pub const CURRENT_VERSION: u32 = 2;
+
+/// 没有 version 字段的文件视为版本 1
+const LEGACY_VERSION: u32 = 1;
+
+/// 每一步把配置从版本 n 升级到 n + 1，下标 0 对应 1 → 2
+const MIGRATIONS: &[fn(&mut Table)] = &[v1_to_v2];
+
+/// 把解析后的配置逐步升级到当前版本，返回文件原来的版本
+pub fn migrate(table: &mut Table) -> Result<u32, AudioError> {
+    let version = match table.get("version") {
+        None => LEGACY_VERSION,
+        Some(Value::Integer(v)) if *v >= LEGACY_VERSION as i64 => *v as u32,
+        Some(other) => {
+            return Err(AudioError::Config(format!(
+                "Invalid config version: {}",
+                other
+            )))
+        }
+    };
+    if version > CURRENT_VERSION {
+        return Err(AudioError::Config(format!(
+            "Config version {} is newer than the supported version {}",
+            version, CURRENT_VERSION
+        )));
+    }
+
+    for (from, step) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .map(|(i, step)| (i as u32 + LEGACY_VERSION, step))
+        .skip_while(|(from, _)| *from < version)
+    {
+        tracing::info!("Migrating config from version {} to {}", from, from + 1);
+        step(table);
+    }
+    table.insert("version".into(), Value::Integer(CURRENT_VERSION as i64));
+    Ok(version)
+}
+
+/// 版本 1 → 2：补齐最初格式中必填的路由字段，把当前设置放入默认档案
+fn v1_to_v2(table: &mut Table) {
+    if let Some(Value::Array(routes)) = table.get_mut("routes") {
+        for route in routes.iter_mut().filter_map(Value::as_table_mut) {
+            route.entry("gain_db").or_insert(Value::Float(0.0));
+            route.entry("enabled").or_insert(Value::Boolean(true));
+        }
+    }
+
+    if !table.contains_key("profiles") {
+        let profile: Table = [
+            "routes",
+            "device_gains",
+            "mix_modes",
+            "limiters",
+            "device_configs",
+        ]
+        .into_iter()
+        .filter_map(|key| table.get(key).map(|v| (key.to_string(), v.clone())))
+        .collect();
+        let name = super::profiles::DEFAULT_PROFILE;
+        let mut profiles = Table::new();
+        profiles.insert(name.into(), Value::Table(profile));
+        table.insert("profiles".into(), Value::Table(profiles));
+        table.insert("active_profile".into(), Value::String(name.into()));
+    }
+}
+
+/// 把 TOML 解析错误转换为带文件名、行号和列号的配置错误
+pub fn parse_error(path: &Path, contents: &str, error: &toml::de::Error) -> AudioError {
+    let location = error.span().map(|span| {
+        let before = &contents[..span.start.min(contents.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        // 列号按字符计数，设备名中的中文不会让列号偏大
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    });
+    match location {
+        Some((line, column)) => AudioError::Config(format!(
+            "{}:{}:{}: {}",
+            path.display(),
+            line,
+            column,
+            error.message()
+        )),
+        None => AudioError::Config(format!("{}: {}", path.display(), error.message())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_config_is_migrated_to_current_version() {
+        let mut table: Table = r#"
+            [[routes]]
+            input_device_id = "mic"
+            output_device_id = "speakers"
+
+            [device_gains]
+            mic = -3.0
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(migrate(&mut table).unwrap(), 1);
+        assert_eq!(table["version"].as_integer(), Some(CURRENT_VERSION as i64));
+        let route = &table["profiles"]["Default"]["routes"][0];
+        assert_eq!(route["enabled"].as_bool(), Some(true));
+        assert_eq!(route["gain_db"].as_float(), Some(0.0));
+
+        // 已是当前版本的配置不再改动
+        let before = table.clone();
+        assert_eq!(migrate(&mut table).unwrap(), CURRENT_VERSION);
+        assert_eq!(table, before);
+    }
+
+    #[test]
+    fn test_newer_versions_and_syntax_errors_are_reported() {
+        let mut table: Table = "version = 99".parse().unwrap();
+        assert!(migrate(&mut table).is_err());
+
+        let contents = "[device_gains]\nmic = -3.0\nspeakers = oops\n";
+        let error = contents.parse::<Table>().unwrap_err();
+        let message = parse_error(Path::new("config.toml"), contents, &error).to_string();
+        assert!(message.contains("config.toml:3:12"), "{message}");
+
+        let contents = "[device_gains]\n\"麦克风\" = oops\n";
+        let error = contents.parse::<Table>().unwrap_err();
+        let message = parse_error(Path::new("config.toml"), contents, &error).to_string();
+        assert!(message.contains("config.toml:2:9"), "{message}");
+    }
+}
@@ -1,2 +1,11 @@
+mod autosave;
+mod migrate;
+mod profiles;
 mod storage;
-pub use storage::{ConfigStorage, AppConfig};
+mod transfer;
+mod watch;
+pub use autosave::{Autosave, AUTOSAVE_DELAY};
+pub use profiles::{Profile, ProfileList};
+pub use storage::{ConfigBackup, ConfigStorage, AppConfig};
+pub use transfer::{export_config, import_config, ImportReport};
+pub use watch::{ConfigEvent, ConfigWatcher, ProfileDiff, CONFIG_POLL_INTERVAL};
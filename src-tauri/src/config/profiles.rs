@@ -0,0 +1,252 @@
+use super::storage::AppConfig;
+use crate::audio::{AudioEngine, AudioError, DeviceConfig, LimiterSettings, MixMode, Route};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// 没有任何档案时，当前设置保存到的档案名
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// 配置档案：一整套路由、增益和各设备设置
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub device_gains: HashMap<String, f32>,
+    #[serde(default)]
+    pub mix_modes: HashMap<String, MixMode>,
+    #[serde(default)]
+    pub limiters: HashMap<String, LimiterSettings>,
+    #[serde(default)]
+    pub device_configs: HashMap<String, DeviceConfig>,
+}
+
+impl Profile {
+    /// 从引擎当前的路由和设备设置生成档案
+    pub fn capture(engine: &AudioEngine) -> Self {
+        Self {
+            routes: engine.get_routes(),
+            device_gains: engine.device_gains.clone(),
+            mix_modes: engine.get_mix_modes(),
+            limiters: engine.get_limiter_settings(),
+            device_configs: engine.get_device_configs(),
+        }
+    }
+
+    /// 用档案整体替换引擎的路由和设备设置。引擎运行中时立即生效：
+    /// 两个档案共有的路由继续播放，其余路由交叉淡化
+    pub fn apply(&self, engine: &mut AudioEngine) -> Result<(), AudioError> {
+        engine.device_gains = self.device_gains.clone();
+        engine.set_mix_modes(self.mix_modes.clone())?;
+        engine.set_limiters(self.limiters.clone())?;
+        // 设备参数先于路由生效，避免设备以旧参数打开后又立即重开
+        let configs = engine.set_device_configs(self.device_configs.clone());
+        let routes = engine.set_routes(self.routes.clone());
+        configs.and(routes)
+    }
+
+    /// 检查设置是否有效，应用到引擎之前调用
+    pub fn validate(&self) -> Result<(), AudioError> {
+        for route in &self.routes {
+            let name = format!("{} -> {}", route.input_device_id, route.output_device_id);
+            if route.input_device_id.is_empty() || route.output_device_id.is_empty() {
+                return Err(AudioError::Config(format!(
+                    "Route {} is missing a device id",
+                    name
+                )));
+            }
+            if !route.gain_db.is_finite() {
+                return Err(AudioError::Config(format!(
+                    "Route {} has an invalid gain {}",
+                    name, route.gain_db
+                )));
+            }
+            if route
+                .channel_map
+                .as_ref()
+                .is_some_and(|map| !map.is_valid())
+            {
+                return Err(AudioError::Config(format!(
+                    "Route {} has an invalid channel map",
+                    name
+                )));
+            }
+        }
+        for (device_id, gain_db) in &self.device_gains {
+            if !gain_db.is_finite() {
+                return Err(AudioError::Config(format!(
+                    "Device {} has an invalid gain {}",
+                    device_id, gain_db
+                )));
+            }
+        }
+        for (device_id, mode) in &self.mix_modes {
+            mode.validate().map_err(|e| match e {
+                AudioError::Config(message) => {
+                    AudioError::Config(format!("{} (output {})", message, device_id))
+                }
+                e => e,
+            })?;
+        }
+        for (device_id, settings) in &self.limiters {
+            settings.validate().map_err(|e| match e {
+                AudioError::Config(message) => {
+                    AudioError::Config(format!("{} (output {})", message, device_id))
+                }
+                e => e,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// 档案中用到的所有设备 id
+    pub fn device_ids(&self) -> BTreeSet<String> {
+        self.routes
+            .iter()
+            .flat_map(|r| [&r.input_device_id, &r.output_device_id])
+            .chain(self.device_gains.keys())
+            .chain(self.mix_modes.keys())
+            .chain(self.limiters.keys())
+            .chain(self.device_configs.keys())
+            .cloned()
+            .collect()
+    }
+
+    /// 把档案中的设备 id `from` 全部替换为 `to`
+    pub fn rename_device(&mut self, from: &str, to: &str) {
+        for route in &mut self.routes {
+            if route.input_device_id == from {
+                route.input_device_id = to.to_string();
+            }
+            if route.output_device_id == from {
+                route.output_device_id = to.to_string();
+            }
+        }
+        rename_key(&mut self.device_gains, from, to);
+        rename_key(&mut self.mix_modes, from, to);
+        rename_key(&mut self.limiters, from, to);
+        rename_key(&mut self.device_configs, from, to);
+    }
+}
+
+fn rename_key<V>(map: &mut HashMap<String, V>, from: &str, to: &str) {
+    if let Some(value) = map.remove(from) {
+        map.insert(to.to_string(), value);
+    }
+}
+
+/// 档案列表
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileList {
+    pub active: String,
+    pub names: Vec<String>,
+}
+
+impl AppConfig {
+    /// 保证至少有一个档案且当前档案存在
+    pub fn normalize_profiles(&mut self) {
+        if self.profiles.is_empty() {
+            let name = if self.active_profile.is_empty() {
+                DEFAULT_PROFILE.to_string()
+            } else {
+                self.active_profile.clone()
+            };
+            self.profiles.insert(name, self.current.clone());
+        }
+        if !self.profiles.contains_key(&self.active_profile) {
+            self.active_profile = self.profiles.keys().next().cloned().unwrap_or_default();
+        }
+    }
+
+    /// 更新当前设置，同时写入当前档案
+    pub fn store_current(&mut self, profile: Profile) {
+        self.normalize_profiles();
+        self.profiles
+            .insert(self.active_profile.clone(), profile.clone());
+        self.current = profile;
+    }
+
+    pub fn profile_list(&self) -> ProfileList {
+        ProfileList {
+            active: self.active_profile.clone(),
+            names: self.profiles.keys().cloned().collect(),
+        }
+    }
+
+    /// 新建一个空档案
+    pub fn create_profile(&mut self, name: &str) -> Result<(), AudioError> {
+        self.check_new_name(name)?;
+        self.profiles.insert(name.to_string(), Profile::default());
+        Ok(())
+    }
+
+    pub fn duplicate_profile(&mut self, source: &str, name: &str) -> Result<(), AudioError> {
+        let profile = self.profile(source)?.clone();
+        self.check_new_name(name)?;
+        self.profiles.insert(name.to_string(), profile);
+        Ok(())
+    }
+
+    pub fn rename_profile(&mut self, name: &str, new_name: &str) -> Result<(), AudioError> {
+        self.profile(name)?;
+        if name == new_name {
+            return Ok(());
+        }
+        self.check_new_name(new_name)?;
+        if let Some(profile) = self.profiles.remove(name) {
+            self.profiles.insert(new_name.to_string(), profile);
+        }
+        if self.active_profile == name {
+            self.active_profile = new_name.to_string();
+        }
+        Ok(())
+    }
+
+    /// 删除档案；当前档案不能删除
+    pub fn delete_profile(&mut self, name: &str) -> Result<(), AudioError> {
+        self.profile(name)?;
+        if self.active_profile == name {
+            return Err(AudioError::Config(format!(
+                "Cannot delete the active profile '{}'",
+                name
+            )));
+        }
+        self.profiles.remove(name);
+        Ok(())
+    }
+
+    /// 切换当前档案，返回要应用到引擎的设置。
+    /// `current` 是切换前引擎的设置，先保存到原来的档案
+    pub fn activate_profile(
+        &mut self,
+        name: &str,
+        current: Profile,
+    ) -> Result<Profile, AudioError> {
+        let profile = self.profile(name)?.clone();
+        self.store_current(current);
+        self.active_profile = name.to_string();
+        self.current = profile.clone();
+        Ok(profile)
+    }
+
+    pub(super) fn profile(&self, name: &str) -> Result<&Profile, AudioError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| AudioError::Config(format!("No profile named '{}'", name)))
+    }
+
+    fn check_new_name(&self, name: &str) -> Result<(), AudioError> {
+        if name.trim().is_empty() {
+            return Err(AudioError::Config(
+                "Profile name must not be empty".to_string(),
+            ));
+        }
+        if self.profiles.contains_key(name) {
+            return Err(AudioError::Config(format!(
+                "A profile named '{}' already exists",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,45 +1,648 @@
+use super::migrate::{self, CURRENT_VERSION};
+use super::profiles::Profile;
+use super::watch::{ConfigEvent, ConfigWatcher, ProfileDiff};
+use crate::audio::{AudioEngine, AudioError};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+/// 配置文件。顶层是当前生效的设置，`profiles` 保存各个配置档案；
+/// 当前档案的内容与顶层保持一致，因此不认识档案的旧版本仍能读取当前设置
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppConfig {
-    pub routes: Vec<crate::audio::Route>,
-    pub device_gains: HashMap<String, f32>,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(flatten)]
+    pub current: Profile,
+    #[serde(default)]
+    pub active_profile: String,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// 导出文件中记录的设备名称（id → 名称），导入到其他机器时用于匹配设备
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub device_names: BTreeMap<String, String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            current: Profile::default(),
+            active_profile: String::new(),
+            profiles: BTreeMap::new(),
+            device_names: BTreeMap::new(),
+        }
+    }
+}
+
+/// 顶层的版本和档案。与当前设置分开解析：经过 flatten 的字段在出错时会丢失位置
+#[derive(Deserialize)]
+struct ConfigHeader {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    active_profile: String,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+    #[serde(default)]
+    device_names: BTreeMap<String, String>,
+}
+
+impl AppConfig {
+    /// 解析 TOML 配置，错误中保留出错的位置
+    pub fn from_toml(contents: &str) -> Result<Self, toml::de::Error> {
+        let current: Profile = toml::from_str(contents)?;
+        let header: ConfigHeader = toml::from_str(contents)?;
+        Ok(Self::from_parts(current, header))
+    }
+
+    /// 解析 JSON 配置，错误中保留出错的位置
+    pub fn from_json(contents: &str) -> Result<Self, serde_json::Error> {
+        let current: Profile = serde_json::from_str(contents)?;
+        let header: ConfigHeader = serde_json::from_str(contents)?;
+        Ok(Self::from_parts(current, header))
+    }
+
+    fn from_parts(current: Profile, header: ConfigHeader) -> Self {
+        Self {
+            version: header.version,
+            current,
+            active_profile: header.active_profile,
+            profiles: header.profiles,
+            device_names: header.device_names,
+        }
+    }
+
+    /// 解析配置文件的内容，旧版本升级到当前版本。返回配置和文件原来的版本；
+    /// 格式错误时返回带文件名、行号和列号的错误
+    pub fn parse(path: &Path, contents: &str) -> Result<(Self, u32), AudioError> {
+        let mut table: toml::Table = contents
+            .parse()
+            .map_err(|e| migrate::parse_error(path, contents, &e))?;
+        let version = migrate::migrate(&mut table)?;
+
+        if version == CURRENT_VERSION {
+            // 直接从原文解析，错误信息中保留位置
+            let config =
+                Self::from_toml(contents).map_err(|e| migrate::parse_error(path, contents, &e))?;
+            return Ok((config, version));
+        }
+
+        let config = toml::to_string(&table)
+            .map_err(|e| e.to_string())
+            .and_then(|migrated| Self::from_toml(&migrated).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                AudioError::Config(format!(
+                    "{}: cannot migrate from version {}: {}",
+                    path.display(),
+                    version,
+                    e
+                ))
+            })?;
+        Ok((config, version))
+    }
+}
+
+/// 保留的配置备份数量
+pub const MAX_BACKUPS: usize = 10;
+
+const CONFIG_FILE: &str = "config.toml";
+const BACKUP_DIR: &str = "backups";
+
+/// 配置文件的一个备份
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ConfigBackup {
+    pub name: String,
+    /// 备份时间（Unix 时间，毫秒）
+    pub created_ms: u64,
 }
 
 pub struct ConfigStorage {
-    config_dir: PathBuf,
+    /// 配置目录；无法使用时为 None，配置只保存在内存中
+    config_dir: Option<PathBuf>,
+    memory: Mutex<Option<AppConfig>>,
+    /// 最近一次读取或写入的文件内容，用于区分程序外的修改和自己的写入
+    known_contents: Mutex<Option<Vec<u8>>>,
+    /// 串行化读-改-写，避免自动保存和命令互相覆盖
+    update_lock: Mutex<()>,
 }
 
 impl ConfigStorage {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_dir = directories::ProjectDirs::from("com", "audioflow", "Audio Flow")
-            .expect("无法获取配置目录")
-            .config_dir()
-            .to_path_buf();
-        
+    /// 使用系统的配置目录。目录无法获取或创建时退回到内存中的配置，
+    /// 程序照常运行，但修改不会保存到磁盘
+    pub fn new() -> Self {
+        let Some(dirs) = directories::ProjectDirs::from("com", "audioflow", "Audio Flow") else {
+            tracing::warn!("No configuration directory available, settings will not be saved");
+            return Self::in_memory();
+        };
+        match Self::with_dir(dirs.config_dir().to_path_buf()) {
+            Ok(storage) => storage,
+            Err(e) => {
+                tracing::warn!(
+                    "Cannot use configuration directory {}: {}, settings will not be saved",
+                    dirs.config_dir().display(),
+                    e
+                );
+                Self::in_memory()
+            }
+        }
+    }
+
+    /// 使用指定的配置目录
+    pub fn with_dir(config_dir: PathBuf) -> Result<Self, AudioError> {
         fs::create_dir_all(&config_dir)?;
-        
-        Ok(Self { config_dir })
-    }
-    
-    pub fn save_config(&self, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = self.config_dir.join("config.toml");
-        let toml_string = toml::to_string_pretty(config)?;
-        fs::write(config_path, toml_string)?;
+        Ok(Self {
+            config_dir: Some(config_dir),
+            memory: Mutex::new(None),
+            known_contents: Mutex::new(None),
+            update_lock: Mutex::new(()),
+        })
+    }
+
+    /// 只在内存中保存配置
+    pub fn in_memory() -> Self {
+        Self {
+            config_dir: None,
+            memory: Mutex::new(None),
+            known_contents: Mutex::new(None),
+            update_lock: Mutex::new(()),
+        }
+    }
+
+    /// 配置文件的路径；只在内存中保存时为 None
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config_dir.as_ref().map(|dir| dir.join(CONFIG_FILE))
+    }
+
+    /// 保存配置。先写入临时文件再替换，写入中途崩溃不会损坏原文件；
+    /// 内容有变化时原文件先移入备份目录，只保留最近 `MAX_BACKUPS` 个备份
+    pub fn save_config(&self, config: &AppConfig) -> Result<(), AudioError> {
+        let config = AppConfig {
+            version: CURRENT_VERSION,
+            ..config.clone()
+        };
+        let Some(config_path) = self.config_path() else {
+            *self.memory.lock() = Some(config);
+            return Ok(());
+        };
+
+        let toml_string =
+            toml::to_string_pretty(&config).map_err(|e| AudioError::Config(e.to_string()))?;
+        if let Ok(previous) = fs::read(&config_path) {
+            if previous != toml_string.as_bytes() {
+                self.backup(&previous)?;
+            }
+        }
+        write_atomic(&config_path, toml_string.as_bytes())?;
+        *self.known_contents.lock() = Some(toml_string.into_bytes());
+        Ok(())
+    }
+
+    /// 现有的备份，最新的在前
+    pub fn list_backups(&self) -> Result<Vec<ConfigBackup>, AudioError> {
+        let Some(dir) = self.backup_dir() else {
+            return Ok(Vec::new());
+        };
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut backups: Vec<ConfigBackup> = fs::read_dir(&dir)?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let created_ms = name
+                    .strip_prefix("config-")?
+                    .strip_suffix(".toml")?
+                    .parse()
+                    .ok()?;
+                Some(ConfigBackup { name, created_ms })
+            })
+            .collect();
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_ms));
+        Ok(backups)
+    }
+
+    /// 用备份替换当前配置并返回恢复的配置；被替换的配置同样会留下备份
+    pub fn restore_backup(&self, name: &str) -> Result<AppConfig, AudioError> {
+        let _guard = self.update_lock.lock();
+        let backup = self
+            .list_backups()?
+            .into_iter()
+            .find(|backup| backup.name == name)
+            .ok_or_else(|| AudioError::Config(format!("No backup named '{}'", name)))?;
+        let path = self.backup_dir().unwrap_or_default().join(&backup.name);
+        let contents = fs::read_to_string(&path)?;
+        let (mut config, _) = AppConfig::parse(&path, &contents)?;
+        config.normalize_profiles();
+        self.save_config(&config)?;
+        tracing::info!("Restored configuration from backup {}", backup.name);
+        Ok(config)
+    }
+
+    fn backup_dir(&self) -> Option<PathBuf> {
+        self.config_dir.as_ref().map(|dir| dir.join(BACKUP_DIR))
+    }
+
+    fn backup(&self, contents: &[u8]) -> Result<(), AudioError> {
+        let Some(dir) = self.backup_dir() else {
+            return Ok(());
+        };
+        fs::create_dir_all(&dir)?;
+
+        // 同一毫秒内多次保存时顺延，保证文件名唯一且按时间排序
+        let mut created_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let mut path = dir.join(format!("config-{}.toml", created_ms));
+        while path.exists() {
+            created_ms += 1;
+            path = dir.join(format!("config-{}.toml", created_ms));
+        }
+        write_atomic(&path, contents)?;
+
+        for old in self.list_backups()?.iter().skip(MAX_BACKUPS) {
+            if let Err(e) = fs::remove_file(dir.join(&old.name)) {
+                tracing::warn!("Failed to remove old backup {}: {}", old.name, e);
+            }
+        }
         Ok(())
     }
-    
-    pub fn load_config(&self) -> Result<Option<AppConfig>, Box<dyn std::error::Error>> {
-        let config_path = self.config_dir.join("config.toml");
+
+    /// 读取配置。旧版本的文件升级到当前版本后写回，原文件保留为
+    /// `config.v{版本}.toml.bak`；格式错误时返回带行号和列号的错误，文件保持不变
+    pub fn load_config(&self) -> Result<Option<AppConfig>, AudioError> {
+        let Some(config_path) = self.config_path() else {
+            return Ok(self.memory.lock().clone());
+        };
         if !config_path.exists() {
             return Ok(None);
         }
-        
-        let contents = fs::read_to_string(config_path)?;
-        let config: AppConfig = toml::from_str(&contents)?;
+
+        let contents = fs::read_to_string(&config_path)?;
+        let (config, version) = AppConfig::parse(&config_path, &contents)?;
+        if version == CURRENT_VERSION {
+            *self.known_contents.lock() = Some(contents.into_bytes());
+            return Ok(Some(config));
+        }
+
+        let backup = config_path.with_file_name(format!("config.v{}.toml.bak", version));
+        fs::copy(&config_path, &backup)?;
+        self.save_config(&config)?;
+        tracing::info!(
+            "Migrated config from version {} to {}, original saved as {}",
+            version,
+            CURRENT_VERSION,
+            backup.display()
+        );
         Ok(Some(config))
     }
+
+    /// 把引擎当前的设置保存为当前档案。配置文件在程序外被修改、
+    /// 监视线程还没有应用时不保存并返回 ConfigPending，避免引擎中的旧设置覆盖这次修改
+    pub fn save_engine(&self, engine: &std::sync::Mutex<AudioEngine>) -> Result<(), AudioError> {
+        let _guard = self.update_lock.lock();
+        if self.has_external_edit() {
+            return Err(AudioError::ConfigPending);
+        }
+        self.update_locked(|config| {
+            let engine = engine
+                .lock()
+                .map_err(|e| AudioError::Config(e.to_string()))?;
+            config.store_current(Profile::capture(&engine));
+            Ok(())
+        })
+    }
+
+    /// 启动后台线程，配置文件在程序外被修改时把新设置应用到引擎
+    pub fn watch<F>(
+        self: &Arc<Self>,
+        engine: Arc<std::sync::Mutex<AudioEngine>>,
+        interval: Duration,
+        on_event: F,
+    ) -> ConfigWatcher
+    where
+        F: FnMut(ConfigEvent) + Send + 'static,
+    {
+        ConfigWatcher::spawn(self.clone(), engine, interval, on_event)
+    }
+
+    /// 检查配置文件是否在程序外被修改。内容有效时只把变化的部分应用到引擎；
+    /// 无效时引擎保持原来的设置，返回带位置的错误，同一内容只报告一次。
+    /// 修改应用到引擎之后才记为已处理
+    pub fn reload_if_changed(&self, engine: &std::sync::Mutex<AudioEngine>) -> Option<ConfigEvent> {
+        let _guard = self.update_lock.lock();
+        let path = self.config_path()?;
+        let contents = fs::read(&path).ok()?;
+        if self.known_contents.lock().as_ref() == Some(&contents) {
+            return None;
+        }
+
+        // 只在内存中升级旧版本，文件在下次保存时写回
+        let config = String::from_utf8(contents.clone())
+            .map_err(|e| AudioError::Config(format!("{}: {}", path.display(), e)))
+            .and_then(|text| {
+                let (mut config, _) = AppConfig::parse(&path, &text)?;
+                config.normalize_profiles();
+                config.current.validate()?;
+                Ok(config)
+            });
+        let config = match config {
+            Ok(config) => config,
+            Err(e) => {
+                *self.known_contents.lock() = Some(contents);
+                tracing::warn!("Ignoring invalid configuration file: {}", e);
+                return Some(ConfigEvent::Rejected {
+                    error: e.to_string(),
+                });
+            }
+        };
+
+        let Ok(mut engine) = engine.lock() else {
+            tracing::error!("Audio engine is unavailable, configuration change not applied");
+            return Some(ConfigEvent::Rejected {
+                error: "Audio engine is unavailable".to_string(),
+            });
+        };
+        let diff = ProfileDiff::between(&Profile::capture(&engine), &config.current);
+        if !diff.is_empty() {
+            // 无法打开的设备由引擎状态报告，其余设置照常生效
+            if let Err(e) = config.current.apply(&mut engine) {
+                tracing::warn!("Configuration file applied with errors: {}", e);
+            }
+        }
+        *self.known_contents.lock() = Some(contents);
+        if diff.is_empty() {
+            return None;
+        }
+        tracing::info!(
+            "Applied external configuration change: {} added, {} removed, {} changed routes",
+            diff.added_routes.len(),
+            diff.removed_routes.len(),
+            diff.changed_routes.len()
+        );
+        Some(ConfigEvent::Applied {
+            profile: config.active_profile,
+            diff,
+        })
+    }
+
+    /// 读取配置（不存在时使用默认配置），修改后写回。
+    /// 需要同时锁定引擎时，先调用本方法再在回调中锁定引擎
+    pub fn update<R>(
+        &self,
+        f: impl FnOnce(&mut AppConfig) -> Result<R, AudioError>,
+    ) -> Result<R, AudioError> {
+        let _guard = self.update_lock.lock();
+        self.update_locked(f)
+    }
+
+    fn update_locked<R>(
+        &self,
+        f: impl FnOnce(&mut AppConfig) -> Result<R, AudioError>,
+    ) -> Result<R, AudioError> {
+        // 程序外的修改作为这次修改的基础写回文件，但仍然算作未处理，
+        // 由监视线程把它应用到引擎
+        let unapplied = self
+            .has_external_edit()
+            .then(|| self.known_contents.lock().clone());
+        let mut config = self.load_config()?.unwrap_or_default();
+        config.normalize_profiles();
+        let result = f(&mut config)?;
+        self.save_config(&config)?;
+        if let Some(known) = unapplied {
+            *self.known_contents.lock() = known;
+        }
+        Ok(result)
+    }
+
+    /// 配置文件在最近一次读写之后被程序外修改过
+    fn has_external_edit(&self) -> bool {
+        let Some(path) = self.config_path() else {
+            return false;
+        };
+        match (&*self.known_contents.lock(), fs::read(path)) {
+            (Some(known), Ok(contents)) => *known != contents,
+            _ => false,
+        }
+    }
+}
+
+impl Default for ConfigStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 先写入同目录下的临时文件并刷到磁盘，再改名替换目标文件，
+/// 任何时刻目标文件要么是旧内容要么是完整的新内容
+pub(super) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let written = fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+
+    // 改名本身也要落盘
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{MixMode, Route, VirtualBackend, VirtualDevice};
+    use crate::config::profiles::DEFAULT_PROFILE;
+    use std::sync::Arc;
+
+    fn virtual_engine() -> AudioEngine {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        backend.add_device(VirtualDevice::output("headset", 48000, 2));
+        AudioEngine::with_backend(Arc::new(backend))
+    }
+
+    #[test]
+    fn test_config_round_trips_through_engine_and_disk() {
+        let engine = std::sync::Mutex::new(virtual_engine());
+        {
+            let mut engine = engine.lock().unwrap();
+            engine.add_route(Route::new("mic", "speakers")).unwrap();
+            engine.set_gain("mic", -6.0).unwrap();
+            engine.set_mix_mode("speakers", MixMode::Sum).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+        assert!(storage.load_config().unwrap().is_none());
+        storage.save_engine(&engine).unwrap();
+
+        let config = storage.load_config().unwrap().unwrap();
+        assert_eq!(config.active_profile, DEFAULT_PROFILE);
+        let mut restored = virtual_engine();
+        config.current.apply(&mut restored).unwrap();
+        let routes = restored.get_routes();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].gain_db, -6.0);
+        assert_eq!(restored.device_gains["mic"], -6.0);
+        assert_eq!(restored.mix_mode("speakers"), MixMode::Sum);
+
+        // 应用空档案清除所有设置
+        Profile::default().apply(&mut restored).unwrap();
+        assert!(restored.get_routes().is_empty());
+        assert_eq!(restored.mix_mode("speakers"), MixMode::default());
+    }
+
+    #[test]
+    fn test_profiles_can_be_managed_and_activated() {
+        let mut engine = virtual_engine();
+        engine.add_route(Route::new("mic", "speakers")).unwrap();
+        engine.start().unwrap();
+
+        let mut config = AppConfig::default();
+        config.store_current(Profile::capture(&engine));
+        config.create_profile("meeting").unwrap();
+        assert!(config.create_profile("meeting").is_err());
+        config
+            .duplicate_profile(DEFAULT_PROFILE, "streaming")
+            .unwrap();
+        config.rename_profile("streaming", "recording").unwrap();
+        assert_eq!(
+            config.profile_list().names,
+            vec![DEFAULT_PROFILE, "meeting", "recording"]
+        );
+
+        // 切换到空档案后在其中添加路由，再切换回来
+        config
+            .activate_profile("meeting", Profile::capture(&engine))
+            .unwrap()
+            .apply(&mut engine)
+            .unwrap();
+        assert!(engine.get_routes().is_empty());
+        engine.add_route(Route::new("mic", "headset")).unwrap();
+        config
+            .activate_profile(DEFAULT_PROFILE, Profile::capture(&engine))
+            .unwrap()
+            .apply(&mut engine)
+            .unwrap();
+        assert_eq!(engine.get_routes()[0].output_device_id, "speakers");
+        assert_eq!(
+            config.profiles["meeting"].routes[0].output_device_id,
+            "headset"
+        );
+        assert!(engine.is_running());
+
+        assert!(config.delete_profile(DEFAULT_PROFILE).is_err());
+        config.delete_profile("recording").unwrap();
+        assert_eq!(
+            config.profile_list().names,
+            vec![DEFAULT_PROFILE, "meeting"]
+        );
+    }
+
+    #[test]
+    fn test_legacy_config_is_upgraded_in_place_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = "[[routes]]\ninput_device_id = \"mic\"\noutput_device_id = \"speakers\"\n\n\
+                      [device_gains]\nmic = -3.0\n";
+        std::fs::write(dir.path().join("config.toml"), legacy).unwrap();
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+
+        let config = storage.load_config().unwrap().unwrap();
+        assert_eq!(config.version, CURRENT_VERSION);
+        assert_eq!(config.active_profile, DEFAULT_PROFILE);
+        assert_eq!(config.current.routes.len(), 1);
+        assert!(config.current.routes[0].enabled);
+        assert_eq!(config.profiles[DEFAULT_PROFILE].device_gains["mic"], -3.0);
+
+        let backup = dir.path().join("config.v1.toml.bak");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), legacy);
+        let upgraded = std::fs::read_to_string(dir.path().join("config.toml")).unwrap();
+        assert!(upgraded.contains("version = 2"));
+    }
+
+    #[test]
+    fn test_malformed_config_is_reported_and_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let broken = "version = 2\n\n[[routes]]\ninput_device_id = \"mic\"\ngain_db = \"loud\"\n";
+        std::fs::write(&path, broken).unwrap();
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+
+        let error = storage.load_config().unwrap_err();
+        assert!(matches!(error, AudioError::Config(_)));
+        assert!(error.to_string().contains("config.toml:5:11:"), "{error}");
+        // 自动保存不会覆盖无法读取的文件
+        assert!(storage.update(|_| Ok(())).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), broken);
+    }
+
+    #[test]
+    fn test_saves_are_atomic_and_keep_rolling_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+        let mut config = AppConfig::default();
+        for i in 0..MAX_BACKUPS + 3 {
+            config
+                .current
+                .device_gains
+                .insert("mic".into(), -(i as f32));
+            storage.save_config(&config).unwrap();
+        }
+        // 内容没有变化时不产生备份
+        storage.save_config(&config).unwrap();
+
+        let backups = storage.list_backups().unwrap();
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert!(backups
+            .windows(2)
+            .all(|w| w[0].created_ms > w[1].created_ms));
+        assert!(!dir.path().join("config.toml.tmp").exists());
+
+        // 最新的备份是倒数第二次保存的内容；恢复后当前配置也留下备份
+        let restored = storage.restore_backup(&backups[0].name).unwrap();
+        let expected = -((MAX_BACKUPS + 1) as f32);
+        assert_eq!(restored.current.device_gains["mic"], expected);
+        let loaded = storage.load_config().unwrap().unwrap();
+        assert_eq!(loaded.current.device_gains["mic"], expected);
+        let latest = &storage.list_backups().unwrap()[0];
+        let contents = std::fs::read_to_string(dir.path().join("backups").join(&latest.name));
+        assert!(contents
+            .unwrap()
+            .contains(&format!("mic = {}.0", -(MAX_BACKUPS as f32 + 2.0))));
+
+        assert!(storage.restore_backup("../config.toml").is_err());
+    }
+
+    #[test]
+    fn test_in_memory_storage_keeps_config_without_a_directory() {
+        let storage = ConfigStorage::in_memory();
+        assert!(storage.config_path().is_none());
+        assert!(storage.load_config().unwrap().is_none());
+
+        let engine = std::sync::Mutex::new(virtual_engine());
+        engine
+            .lock()
+            .unwrap()
+            .add_route(Route::new("mic", "speakers"))
+            .unwrap();
+        storage.save_engine(&engine).unwrap();
+        let config = storage.load_config().unwrap().unwrap();
+        assert_eq!(config.current.routes.len(), 1);
+        assert!(storage.list_backups().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,266 @@
+use super::migrate::CURRENT_VERSION;
+use super::storage::{write_atomic, AppConfig};
+use crate::audio::{match_device, AudioError, DeviceInfo};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// 导入导出文件的格式，由扩展名决定：`.json` 为 JSON，其余为 TOML
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    Toml,
+    Json,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+}
+
+/// 导入配置的结果
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// 导入后的档案名（与本机档案重名时已改名）
+    pub profiles: Vec<String>,
+    /// 本机不存在、已映射到本机设备的 id
+    pub remapped: BTreeMap<String, String>,
+    /// 本机找不到对应设备的 id，保持原样
+    pub unresolved: Vec<String>,
+}
+
+/// 把配置写入导出文件
+pub fn export_config(path: &Path, config: &AppConfig) -> Result<(), AudioError> {
+    let contents = match FileFormat::from_path(path) {
+        FileFormat::Toml => toml::to_string_pretty(config).map_err(|e| e.to_string()),
+        FileFormat::Json => serde_json::to_string_pretty(config).map_err(|e| e.to_string()),
+    }
+    .map_err(AudioError::Config)?;
+    write_atomic(path, contents.as_bytes())?;
+    Ok(())
+}
+
+/// 读取导出文件。TOML 文件与配置文件一样会升级旧版本；
+/// JSON 导出从版本 2 开始才有，不需要升级
+pub fn import_config(path: &Path) -> Result<AppConfig, AudioError> {
+    let contents = fs::read_to_string(path)?;
+    let mut config = match FileFormat::from_path(path) {
+        FileFormat::Toml => AppConfig::parse(path, &contents)?.0,
+        FileFormat::Json => AppConfig::from_json(&contents).map_err(|e| {
+            // serde_json 的错误信息末尾带有位置，改为与 TOML 错误相同的格式
+            let message = e.to_string();
+            let suffix = format!(" at line {} column {}", e.line(), e.column());
+            AudioError::Config(format!(
+                "{}:{}:{}: {}",
+                path.display(),
+                e.line(),
+                e.column(),
+                message.strip_suffix(&suffix).unwrap_or(&message)
+            ))
+        })?,
+    };
+    if config.version > CURRENT_VERSION {
+        return Err(AudioError::Config(format!(
+            "Config version {} is newer than the supported version {}",
+            config.version, CURRENT_VERSION
+        )));
+    }
+    config.version = CURRENT_VERSION;
+    config.normalize_profiles();
+    Ok(config)
+}
+
+impl AppConfig {
+    /// 生成导出用的配置：`profile` 为 None 时导出全部档案，否则只导出该档案。
+    /// 同时记录用到的设备名称，供其他机器导入时匹配设备
+    pub fn export(
+        &self,
+        profile: Option<&str>,
+        devices: &[DeviceInfo],
+    ) -> Result<Self, AudioError> {
+        let mut exported = match profile {
+            None => self.clone(),
+            Some(name) => {
+                let profile = self.profile(name)?.clone();
+                Self {
+                    current: profile.clone(),
+                    active_profile: name.to_string(),
+                    profiles: BTreeMap::from([(name.to_string(), profile)]),
+                    ..Self::default()
+                }
+            }
+        };
+        exported.version = CURRENT_VERSION;
+        exported.device_names = exported
+            .device_ids()
+            .into_iter()
+            .filter_map(|id| {
+                let device = devices.iter().find(|d| d.id == id)?;
+                Some((id, device.name.clone()))
+            })
+            .collect();
+        Ok(exported)
+    }
+
+    /// 当前设置和所有档案中用到的设备 id
+    pub fn device_ids(&self) -> BTreeSet<String> {
+        let mut ids = self.current.device_ids();
+        for profile in self.profiles.values() {
+            ids.extend(profile.device_ids());
+        }
+        ids
+    }
+
+    /// 把本机不存在的设备 id 映射到本机设备：先按 id，再按导出时记录的设备名称匹配。
+    /// 找不到的 id 保持原样并在结果中列出
+    pub fn remap_devices(&mut self, devices: &[DeviceInfo]) -> ImportReport {
+        let mut report = ImportReport::default();
+        for id in self.device_ids() {
+            let name = self.device_names.get(&id).map(String::as_str);
+            match match_device(&id, name, devices) {
+                Some(local) if local == id => {}
+                Some(local) => {
+                    report.remapped.insert(id, local);
+                }
+                None => report.unresolved.push(id),
+            }
+        }
+
+        for (from, to) in &report.remapped {
+            tracing::info!("Imported device {} mapped to {}", from, to);
+            self.current.rename_device(from, to);
+            for profile in self.profiles.values_mut() {
+                profile.rename_device(from, to);
+            }
+        }
+        self.device_names.clear();
+        report
+    }
+
+    /// 把导入的档案加入本机配置，与已有档案重名的改名为 `{名称} (imported)`。
+    /// 返回导入后的档案名，以及导入文件中当前档案的新名称
+    pub fn import_profiles(&mut self, imported: AppConfig) -> (Vec<String>, String) {
+        let mut names = Vec::new();
+        let mut active = String::new();
+        for (name, profile) in imported.profiles {
+            let new_name = self.unused_profile_name(&name);
+            if name == imported.active_profile {
+                active = new_name.clone();
+            }
+            self.profiles.insert(new_name.clone(), profile);
+            names.push(new_name);
+        }
+        (names, active)
+    }
+
+    fn unused_profile_name(&self, name: &str) -> String {
+        if !self.profiles.contains_key(name) {
+            return name.to_string();
+        }
+        let mut candidate = format!("{} (imported)", name);
+        let mut n = 2;
+        while self.profiles.contains_key(&candidate) {
+            candidate = format!("{} (imported {})", name, n);
+            n += 1;
+        }
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioEngine, Route, VirtualBackend, VirtualDevice};
+    use crate::config::Profile;
+    use std::sync::Arc;
+
+    fn device(id: &str, name: &str, is_input: bool) -> VirtualDevice {
+        let device = if is_input {
+            VirtualDevice::input(id, 48000, 2)
+        } else {
+            VirtualDevice::output(id, 48000, 2)
+        };
+        VirtualDevice {
+            name: name.to_string(),
+            ..device
+        }
+    }
+
+    fn engine_with(devices: Vec<VirtualDevice>) -> AudioEngine {
+        let backend = VirtualBackend::new();
+        for device in devices {
+            backend.add_device(device);
+        }
+        AudioEngine::with_backend(Arc::new(backend))
+    }
+
+    #[test]
+    fn test_exported_profile_is_remapped_on_another_machine() {
+        // 导出机器上的设备
+        let mut source = engine_with(vec![
+            device("input:{1111}", "USB Microphone", true),
+            device("output:{2222}", "Studio Monitors", false),
+            device("output:{3333}", "Office Headset", false),
+        ]);
+        source
+            .add_route(Route::new("input:{1111}", "output:{2222}"))
+            .unwrap();
+        source
+            .add_route(Route::new("input:{1111}", "output:{3333}"))
+            .unwrap();
+        source.set_gain("input:{1111}", -6.0).unwrap();
+        let mut config = AppConfig::default();
+        config.store_current(Profile::capture(&source));
+        config.create_profile("empty").unwrap();
+
+        let devices = source.device_manager.list_devices().unwrap();
+        let exported = config.export(Some("Default"), &devices).unwrap();
+        assert_eq!(exported.profile_list().names, vec!["Default"]);
+        assert_eq!(exported.device_names["output:{2222}"], "Studio Monitors");
+
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["setup.toml", "setup.json"] {
+            let path = dir.path().join(file);
+            export_config(&path, &exported).unwrap();
+            let mut imported = import_config(&path).unwrap();
+
+            // 另一台机器上设备 id 不同，名称相同或相近；没有耳机
+            let target = engine_with(vec![
+                device("input:{aaaa}", "USB Microphone", true),
+                device("output:{bbbb}", "Studio Monitors (2)", false),
+            ]);
+            let report = imported.remap_devices(&target.device_manager.list_devices().unwrap());
+            assert_eq!(report.remapped["input:{1111}"], "input:{aaaa}");
+            assert_eq!(report.remapped["output:{2222}"], "output:{bbbb}");
+            assert_eq!(report.unresolved, vec!["output:{3333}"]);
+
+            let profile = &imported.profiles["Default"];
+            assert_eq!(profile.device_gains["input:{aaaa}"], -6.0);
+            assert!(profile
+                .routes
+                .iter()
+                .any(|r| r.input_device_id == "input:{aaaa}"
+                    && r.output_device_id == "output:{bbbb}"));
+
+            // 与本机档案重名的档案改名后加入
+            let mut local = AppConfig::default();
+            local.normalize_profiles();
+            let (names, active) = local.import_profiles(imported);
+            assert_eq!(names, vec!["Default (imported)"]);
+            assert_eq!(active, "Default (imported)");
+        }
+    }
+
+    #[test]
+    fn test_invalid_import_reports_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.json");
+        fs::write(&path, "{\n  \"routes\": 3\n}").unwrap();
+        let error = import_config(&path).unwrap_err().to_string();
+        assert!(error.contains("setup.json:2:"), "{error}");
+    }
+}
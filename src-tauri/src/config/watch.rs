@@ -0,0 +1,278 @@
+use super::profiles::Profile;
+use super::storage::ConfigStorage;
+use crate::audio::{AudioEngine, Route};
+use crate::poller::Poller;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 默认的配置文件检查间隔
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 两份设置之间的差异
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ProfileDiff {
+    pub added_routes: Vec<Route>,
+    pub removed_routes: Vec<Route>,
+    /// 两边都有但参数（增益、启用状态等）不同的路由，取新值
+    pub changed_routes: Vec<Route>,
+    /// 增益有变化的设备，取新值；被删除的增益记为 0 dB
+    pub device_gains: BTreeMap<String, f32>,
+    /// 混音模式、限制器或设备参数是否有变化
+    pub device_settings_changed: bool,
+}
+
+impl ProfileDiff {
+    pub fn between(old: &Profile, new: &Profile) -> Self {
+        let same_endpoints = |a: &Route, b: &Route| {
+            a.input_device_id == b.input_device_id && a.output_device_id == b.output_device_id
+        };
+
+        let mut diff = Self::default();
+        for route in &new.routes {
+            match old.routes.iter().find(|r| same_endpoints(r, route)) {
+                None => diff.added_routes.push(route.clone()),
+                Some(previous) if previous != route => diff.changed_routes.push(route.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed_routes = old
+            .routes
+            .iter()
+            .filter(|r| !new.routes.iter().any(|route| same_endpoints(r, route)))
+            .cloned()
+            .collect();
+
+        for (device_id, gain_db) in &new.device_gains {
+            if old.device_gains.get(device_id) != Some(gain_db) {
+                diff.device_gains.insert(device_id.clone(), *gain_db);
+            }
+        }
+        for device_id in old.device_gains.keys() {
+            if !new.device_gains.contains_key(device_id) {
+                diff.device_gains.insert(device_id.clone(), 0.0);
+            }
+        }
+
+        diff.device_settings_changed = old.mix_modes != new.mix_modes
+            || old.limiters != new.limiters
+            || old.device_configs != new.device_configs;
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 配置文件在程序外被修改后的处理结果，作为 Tauri 事件发给前端
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ConfigEvent {
+    /// 新的设置已应用到引擎
+    Applied { profile: String, diff: ProfileDiff },
+    /// 文件无效，引擎保持原来的设置
+    Rejected { error: String },
+}
+
+impl ConfigEvent {
+    /// 对应的 Tauri 事件名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Applied { .. } => "config-applied",
+            Self::Rejected { .. } => "config-rejected",
+        }
+    }
+}
+
+/// 后台线程，定期检查配置文件是否在程序外被修改并应用到引擎；drop 时停止线程
+pub struct ConfigWatcher {
+    poller: Poller,
+}
+
+impl ConfigWatcher {
+    pub fn spawn<F>(
+        storage: Arc<ConfigStorage>,
+        engine: Arc<Mutex<AudioEngine>>,
+        interval: Duration,
+        mut on_event: F,
+    ) -> Self
+    where
+        F: FnMut(ConfigEvent) + Send + 'static,
+    {
+        let poller = Poller::spawn("config-watcher", interval, move || {
+            if let Some(event) = storage.reload_if_changed(&engine) {
+                on_event(event);
+            }
+            // 引擎锁中毒后无法再应用修改，与恢复线程一样退出
+            !engine.is_poisoned()
+        });
+
+        Self { poller }
+    }
+
+    pub fn stop(&mut self) {
+        self.poller.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{AudioError, VirtualBackend, VirtualDevice};
+    use crate::config::AppConfig;
+
+    const EDITED: &str = r#"
+version = 2
+
+[[routes]]
+input_device_id = "mic"
+output_device_id = "speakers"
+gain_db = -6.0
+enabled = true
+
+[[routes]]
+input_device_id = "mic"
+output_device_id = "headset"
+gain_db = 0.0
+enabled = true
+"#;
+
+    #[test]
+    fn test_external_edits_are_applied_and_invalid_files_rejected() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        backend.add_device(VirtualDevice::output("headset", 48000, 2));
+        let engine = Mutex::new(AudioEngine::with_backend(Arc::new(backend)));
+        {
+            let mut engine = engine.lock().unwrap();
+            engine.add_route(Route::new("mic", "speakers")).unwrap();
+            engine.start().unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+        storage.save_engine(&engine).unwrap();
+        // 自己写入的内容不算外部修改
+        assert!(storage.reload_if_changed(&engine).is_none());
+
+        std::fs::write(&path, EDITED).unwrap();
+        let Some(ConfigEvent::Applied { diff, .. }) = storage.reload_if_changed(&engine) else {
+            panic!("edit was not applied");
+        };
+        assert_eq!(diff.added_routes.len(), 1);
+        assert_eq!(diff.changed_routes[0].gain_db, -6.0);
+        assert!(diff.removed_routes.is_empty());
+        {
+            let engine = engine.lock().unwrap();
+            assert_eq!(engine.get_routes().len(), 2);
+            assert!(engine.is_running());
+        }
+        assert!(storage.reload_if_changed(&engine).is_none());
+        storage.save_engine(&engine).unwrap();
+        assert!(storage.reload_if_changed(&engine).is_none());
+
+        // 语法错误和无效的设置都被拒绝，引擎保持原来的路由，同一内容只报告一次
+        let invalid = [
+            EDITED.replace("gain_db = -6.0", "gain_db = \"loud\""),
+            format!("{EDITED}\n[limiters.speakers]\nceiling_db = 3.0\n"),
+        ];
+        for contents in invalid {
+            std::fs::write(&path, &contents).unwrap();
+            let Some(ConfigEvent::Rejected { error }) = storage.reload_if_changed(&engine) else {
+                panic!("invalid file was not rejected");
+            };
+            assert!(
+                error.contains("config.toml:7:11") || error.contains("ceiling"),
+                "{error}"
+            );
+            assert!(storage.reload_if_changed(&engine).is_none());
+            assert_eq!(engine.lock().unwrap().get_routes().len(), 2);
+        }
+
+        let removed = EDITED
+            .split("\n[[routes]]")
+            .take(2)
+            .collect::<Vec<_>>()
+            .join("\n[[routes]]");
+        std::fs::write(&path, removed).unwrap();
+        let Some(ConfigEvent::Applied { diff, .. }) = storage.reload_if_changed(&engine) else {
+            panic!("edit was not applied");
+        };
+        assert_eq!(diff.removed_routes[0].output_device_id, "headset");
+        assert_eq!(engine.lock().unwrap().get_routes().len(), 1);
+    }
+
+    #[test]
+    fn test_saves_before_the_next_poll_do_not_overwrite_external_edits() {
+        let backend = VirtualBackend::new();
+        backend.add_device(VirtualDevice::input("mic", 48000, 2));
+        backend.add_device(VirtualDevice::output("speakers", 48000, 2));
+        backend.add_device(VirtualDevice::output("headset", 48000, 2));
+        let engine = Mutex::new(AudioEngine::with_backend(Arc::new(backend)));
+        engine
+            .lock()
+            .unwrap()
+            .add_route(Route::new("mic", "speakers"))
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+        storage.save_engine(&engine).unwrap();
+
+        // 修改文件后立即自动保存和修改档案，中间没有检查
+        std::fs::write(&path, EDITED).unwrap();
+        assert!(matches!(
+            storage.save_engine(&engine),
+            Err(AudioError::ConfigPending)
+        ));
+        storage
+            .update(|config| config.create_profile("live"))
+            .unwrap();
+        let on_disk = AppConfig::from_toml(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk.current.routes.len(), 2);
+        assert!(on_disk.profiles.contains_key("live"));
+
+        let Some(ConfigEvent::Applied { diff, .. }) = storage.reload_if_changed(&engine) else {
+            panic!("edit was not applied");
+        };
+        assert_eq!(diff.added_routes.len(), 1);
+        assert_eq!(engine.lock().unwrap().get_routes().len(), 2);
+
+        // 应用之后正常保存
+        engine.lock().unwrap().set_gain("mic", -3.0).unwrap();
+        storage.save_engine(&engine).unwrap();
+        assert!(storage.reload_if_changed(&engine).is_none());
+        let saved = storage.load_config().unwrap().unwrap();
+        assert_eq!(saved.current.device_gains["mic"], -3.0);
+    }
+
+    #[test]
+    fn test_edit_is_not_marked_handled_when_the_engine_is_unavailable() {
+        let engine = Arc::new(Mutex::new(AudioEngine::with_backend(Arc::new(
+            VirtualBackend::new(),
+        ))));
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ConfigStorage::with_dir(dir.path().to_path_buf()).unwrap();
+        storage.save_engine(&engine).unwrap();
+
+        let poisoned = Arc::clone(&engine);
+        let _ = std::thread::spawn(move || {
+            let _engine = poisoned.lock().unwrap();
+            panic!("poison the engine lock");
+        })
+        .join();
+
+        std::fs::write(dir.path().join("config.toml"), EDITED).unwrap();
+        for _ in 0..2 {
+            assert!(matches!(
+                storage.reload_if_changed(&engine),
+                Some(ConfigEvent::Rejected { .. })
+            ));
+        }
+    }
+}
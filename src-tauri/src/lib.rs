@@ -1,6 +1,8 @@
-mod audio;
+pub mod audio;
+#[cfg(feature = "gui")]
 pub mod commands;
-mod config;
+pub mod config;
+mod poller;
 mod state;
 
 pub use state::AppState;
@@ -1,6 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 fn main() {
     tauri::Builder::default()
@@ -11,7 +11,46 @@ fn main() {
             tracing::info!("Starting Audio Flow v0.1.0");
 
             let state = audio_flow::AppState::new();
+
+            // 设备热插拔和默认设备变化以事件形式通知前端
+            let handle = app.handle().clone();
+            let watcher = state.engine.lock().unwrap().device_manager.watch(
+                audio_flow::audio::watcher::DEFAULT_POLL_INTERVAL,
+                move |event| {
+                    if let Err(e) = handle.emit(event.name(), &event) {
+                        tracing::warn!("Failed to emit {}: {}", event.name(), e);
+                    }
+                },
+            );
+
+            // 设备出错后自动重试，引擎状态变化时通知前端
+            let handle = app.handle().clone();
+            let monitor = audio_flow::audio::RecoveryMonitor::spawn(
+                state.engine.clone(),
+                audio_flow::audio::recovery::DEFAULT_CHECK_INTERVAL,
+                move |status| {
+                    if let Err(e) = handle.emit("engine-status-changed", &status) {
+                        tracing::warn!("Failed to emit engine-status-changed: {}", e);
+                    }
+                },
+            );
+
+            // 配置文件在程序外被修改时应用到引擎，结果通知前端
+            let handle = app.handle().clone();
+            let config_watcher = state.storage.watch(
+                state.engine.clone(),
+                audio_flow::config::CONFIG_POLL_INTERVAL,
+                move |event| {
+                    if let Err(e) = handle.emit(event.name(), &event) {
+                        tracing::warn!("Failed to emit {}: {}", event.name(), e);
+                    }
+                },
+            );
+
             app.manage(state);
+            app.manage(watcher);
+            app.manage(monitor);
+            app.manage(config_watcher);
 
             Ok(())
         })
@@ -19,11 +58,39 @@ fn main() {
             audio_flow::commands::list_devices,
             audio_flow::commands::add_route,
             audio_flow::commands::remove_route,
+            audio_flow::commands::set_route_enabled,
             audio_flow::commands::set_gain,
             audio_flow::commands::start_engine,
             audio_flow::commands::stop_engine,
+            audio_flow::commands::restart_engine,
+            audio_flow::commands::get_engine_status,
             audio_flow::commands::get_peak_levels,
             audio_flow::commands::get_routes,
+            audio_flow::commands::set_target_latency,
+            audio_flow::commands::set_ramp_time,
+            audio_flow::commands::set_mix_mode,
+            audio_flow::commands::get_mix_modes,
+            audio_flow::commands::set_limiter,
+            audio_flow::commands::get_limiters,
+            audio_flow::commands::get_gain_reduction,
+            audio_flow::commands::get_device_capabilities,
+            audio_flow::commands::list_device_capabilities,
+            audio_flow::commands::set_device_config,
+            audio_flow::commands::get_device_configs,
+            audio_flow::commands::get_stream_health,
+            audio_flow::commands::save_config,
+            audio_flow::commands::load_config,
+            audio_flow::commands::reset_config,
+            audio_flow::commands::list_profiles,
+            audio_flow::commands::create_profile,
+            audio_flow::commands::duplicate_profile,
+            audio_flow::commands::rename_profile,
+            audio_flow::commands::delete_profile,
+            audio_flow::commands::activate_profile,
+            audio_flow::commands::export_config,
+            audio_flow::commands::import_config,
+            audio_flow::commands::list_config_backups,
+            audio_flow::commands::restore_config_backup,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
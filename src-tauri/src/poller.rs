@@ -0,0 +1,62 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 带停止通道的后台线程。stop 或 drop 时断开通道并等待线程退出
+pub(crate) struct Poller {
+    sender: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Poller {
+    /// 每隔 interval 调用一次 tick，直到停止；tick 返回 false 时线程提前退出
+    pub fn spawn<F>(name: &str, interval: Duration, mut tick: F) -> Self
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        Self::spawn_with(name, move |stopped| {
+            // 收到停止信号或发送端被 drop 时退出
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if !tick() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// 线程自己处理通道：notify 发送的消息和停止时的断开都由 run 接收
+    pub fn spawn_with<F>(name: &str, run: F) -> Self
+    where
+        F: FnOnce(Receiver<()>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name(name.into())
+            .spawn(move || run(receiver))
+            .unwrap_or_else(|e| panic!("failed to spawn {} thread: {}", name, e));
+
+        Self {
+            sender: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    pub fn notify(&self) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,15 +1,52 @@
 use crate::audio::engine::AudioEngine;
+use crate::config::{Autosave, ConfigStorage, AUTOSAVE_DELAY};
 use std::sync::{Arc, Mutex};
-use tauri::State;
 
 pub struct AppState {
     pub engine: Arc<Mutex<AudioEngine>>,
+    pub storage: Arc<ConfigStorage>,
+    autosave: Autosave,
 }
 
 impl AppState {
+    /// 创建引擎并加载上次保存的配置
     pub fn new() -> Self {
+        let engine = Arc::new(Mutex::new(AudioEngine::new()));
+
+        let storage = Arc::new(ConfigStorage::new());
+        match storage.load_config() {
+            Ok(Some(config)) => {
+                let mut engine = engine.lock().unwrap();
+                match config.current.apply(&mut engine) {
+                    Ok(()) => tracing::info!(
+                        "Loaded profile '{}' with {} routes",
+                        config.active_profile,
+                        config.current.routes.len()
+                    ),
+                    Err(e) => tracing::warn!("Some saved settings could not be applied: {}", e),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to load configuration: {}", e),
+        }
+
+        let autosave = Autosave::spawn(engine.clone(), storage.clone(), AUTOSAVE_DELAY);
+
         Self {
-            engine: Arc::new(Mutex::new(AudioEngine::new())),
+            engine,
+            storage,
+            autosave,
         }
     }
+
+    /// 路由或设备设置修改后调用，稍后自动保存
+    pub fn config_changed(&self) {
+        self.autosave.notify();
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -1,6 +1,7 @@
 export interface DeviceInfo {
   id: string
   name: string
+  backend_id: string | null
   is_input: boolean
   is_output: boolean
   sample_rate: number
@@ -8,11 +9,140 @@ export interface DeviceInfo {
   is_vb_cable: boolean
 }
 
+export type SampleFormat = 'f32' | 'i16' | 'i32' | 'u16' | 'u8'
+
+export interface StreamConfig {
+  sample_rate: number
+  channels: number
+  buffer_frames: number | null
+  sample_format: SampleFormat
+}
+
+export interface SupportedConfigRange {
+  channels: number
+  min_sample_rate: number
+  max_sample_rate: number
+  min_buffer_frames: number | null
+  max_buffer_frames: number | null
+  sample_format: SampleFormat
+}
+
+export interface DeviceCapabilities {
+  device_id: string
+  name: string
+  is_input: boolean
+  default_config: StreamConfig
+  supported_configs: SupportedConfigRange[]
+  sample_formats: SampleFormat[]
+  min_sample_rate: number
+  max_sample_rate: number
+  channel_counts: number[]
+  min_buffer_frames: number | null
+  max_buffer_frames: number | null
+}
+
+// 未设置的项使用设备默认值
+export interface DeviceConfig {
+  sample_rate?: number | null
+  buffer_frames?: number | null
+  channels?: number | null
+}
+
 export interface Route {
   input_device_id: string
   output_device_id: string
   gain_db: number
   enabled: boolean
+  resample_quality?: 'linear' | 'sinc'
+  channel_map?: ChannelMap | null
+}
+
+export interface ChannelMap {
+  // matrix[out][in]
+  matrix: number[][]
+}
+
+export type MixMode =
+  | { type: 'sum' }
+  | { type: 'headroom'; headroom_db: number }
+  | { type: 'equal_power' }
+  | { type: 'average' }
+
+export interface LimiterSettings {
+  enabled: boolean
+  threshold_db: number
+  ceiling_db: number
+  release_ms: number
 }
 
 export type PeakLevels = Record<string, number>
+
+// 每个输出设备的限制器增益衰减（dB）
+export type GainReduction = Record<string, number>
+
+export type DeviceEvent =
+  | { type: 'added'; device: DeviceInfo }
+  | { type: 'removed'; device: DeviceInfo }
+  | { type: 'default_changed'; is_input: boolean; device_id: string | null }
+
+export interface DeviceFailure {
+  device_id: string
+  error: string
+  attempts: number
+  retry_in_ms: number
+}
+
+export interface StreamHealth {
+  failed_devices: DeviceFailure[]
+  degraded_routes: Route[]
+}
+
+export type EngineState = 'stopped' | 'starting' | 'running' | 'degraded' | 'stopping'
+
+export interface EngineStatus extends StreamHealth {
+  state: EngineState
+}
+
+export interface Profile {
+  routes: Route[]
+  device_gains: Record<string, number>
+  mix_modes: Record<string, MixMode>
+  limiters: Record<string, LimiterSettings>
+  device_configs: Record<string, DeviceConfig>
+}
+
+export interface AppConfig extends Profile {
+  version: number
+  active_profile: string
+  profiles: Record<string, Profile>
+  /** 导出文件中记录的设备名称，导入时用于匹配本机设备 */
+  device_names?: Record<string, string>
+}
+
+export interface ProfileList {
+  active: string
+  names: string[]
+}
+
+export interface ConfigBackup {
+  name: string
+  created_ms: number
+}
+
+export interface ProfileDiff {
+  added_routes: Route[]
+  removed_routes: Route[]
+  changed_routes: Route[]
+  device_gains: Record<string, number>
+  device_settings_changed: boolean
+}
+
+export type ConfigEvent =
+  | { type: 'applied'; profile: string; diff: ProfileDiff }
+  | { type: 'rejected'; error: string }
+
+export interface ImportReport {
+  profiles: string[]
+  remapped: Record<string, string>
+  unresolved: string[]
+}